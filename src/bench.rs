@@ -0,0 +1,24 @@
+use rand::{rngs::StdRng, SeedableRng};
+use std::time::Instant;
+
+/// seeded generator for synthetic benchmark data
+pub fn rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// runs the old and new versions once each, printing how long both took
+///
+/// benchmarks are ignored tests, run with
+/// cargo test --release bench_ -- --ignored --nocapture
+pub fn compare<A, B>(name: &str, old: impl FnOnce() -> A, new: impl FnOnce() -> B) -> (A, B) {
+    let start = Instant::now();
+    let old = old();
+    let old_time = start.elapsed();
+
+    let start = Instant::now();
+    let new = new();
+    let new_time = start.elapsed();
+
+    println!("{name}: {old_time:?} before, {new_time:?} after");
+    (old, new)
+}
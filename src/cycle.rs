@@ -0,0 +1,125 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Where the states of a repeated step start to loop
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cycle {
+    /// steps before the first state that repeats
+    pub mu: usize,
+    /// length of the loop
+    pub lambda: usize,
+}
+
+impl Cycle {
+    /// the earliest step that lands on the same state as step n
+    pub fn index(&self, n: usize) -> usize {
+        if n < self.mu {
+            n
+        } else {
+            self.mu + (n - self.mu) % self.lambda
+        }
+    }
+}
+
+/// Brent's algorithm, keeping only two states in memory
+#[allow(dead_code)]
+pub fn brent<T: Clone + PartialEq>(start: &T, step: impl Fn(&T) -> T) -> Cycle {
+    // find the loop length by teleporting the tortoise at powers of two
+    let mut power = 1;
+    let mut lambda = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    while tortoise != hare {
+        if power == lambda {
+            tortoise = hare.clone();
+            power *= 2;
+            lambda = 0;
+        }
+        hare = step(&hare);
+        lambda += 1;
+    }
+
+    // a lambda head start makes them meet where the loop begins
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    for _ in 0..lambda {
+        hare = step(&hare);
+    }
+    let mut mu = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        mu += 1;
+    }
+
+    Cycle { mu, lambda }
+}
+
+/// state after n steps, using Brent's algorithm to skip whole loops
+#[allow(dead_code)]
+pub fn fast_forward<T: Clone + PartialEq>(start: &T, step: impl Fn(&T) -> T, n: usize) -> T {
+    let cycle = brent(start, &step);
+    (0..cycle.index(n)).fold(start.clone(), |state, _| step(&state))
+}
+
+/// remembers each state by hash, returning the states before the first repeat
+pub fn detect<T: Clone + Hash + Eq>(start: T, mut step: impl FnMut(&T) -> T) -> (Cycle, Vec<T>) {
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    let mut states = Vec::new();
+    let mut state = start;
+
+    loop {
+        if let Some(&mu) = seen.get(&state) {
+            let lambda = states.len() - mu;
+            return (Cycle { mu, lambda }, states);
+        }
+        seen.insert(state.clone(), states.len());
+        let next = step(&state);
+        states.push(state);
+        state = next;
+    }
+}
+
+/// state after n steps, read back from the states remembered by `detect`
+#[allow(dead_code)]
+pub fn nth_state<T: Clone + Hash + Eq>(start: T, step: impl FnMut(&T) -> T, n: usize) -> T {
+    let (cycle, mut states) = detect(start, step);
+    states.swap_remove(cycle.index(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(x: &u32) -> u32 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn test_cycle() {
+        let states = (0..100)
+            .scan(3, |x, _| {
+                let current = *x;
+                *x = step(x);
+                Some(current)
+            })
+            .collect::<Vec<_>>();
+
+        let cycle = brent(&3, step);
+        let (hashed, _) = detect(3, step);
+        assert_eq!(cycle, hashed);
+        assert_eq!(states[cycle.mu], states[cycle.mu + cycle.lambda]);
+        assert!(states[..cycle.mu + cycle.lambda]
+            .iter()
+            .enumerate()
+            .all(|(i, state)| !states[i + 1..cycle.mu + cycle.lambda].contains(state)));
+
+        for n in [0, 5, 99] {
+            assert_eq!(fast_forward(&3, step, n), states[n]);
+            assert_eq!(nth_state(3, step, n), states[n]);
+        }
+        assert_eq!(
+            nth_state(3, step, 1_000_000_000),
+            states[cycle.index(1_000_000_000)]
+        );
+    }
+}
@@ -2,7 +2,7 @@ use std::str;
 
 type Input<'a> = Vec<&'a str>;
 
-pub fn generator(input: &str) -> Input {
+pub fn generator(input: &str) -> Input<'_> {
     input.lines().collect()
 }
 
@@ -68,7 +68,7 @@ fn find_numbers(line: &str) -> Vec<char> {
             })
         });
 
-    found_numbers.sort_by(|a, b| a.index.cmp(&b.index));
+    found_numbers.sort_by_key(|a| a.index);
     found_numbers.iter().map(|each| each.number).collect()
 }
 
@@ -51,7 +51,7 @@ impl Part {
     }
 }
 
-pub fn generator(input: &str) -> Input {
+pub fn generator(input: &str) -> Input<'_> {
     let re = Regex::new(r"\d+").unwrap();
 
     (
@@ -10,12 +10,12 @@ pub struct Game {
     winners: usize,
 }
 
-pub fn generator(input: &str) -> Input {
+pub fn generator(input: &str) -> Input<'_> {
     input
         .lines()
         .enumerate()
         .map(|(index, line)| {
-            let mut split = line.split(':').last().unwrap().split('|');
+            let mut split = line.split(':').next_back().unwrap().split('|');
             let winners: HashSet<&str> = split.next().unwrap().split_whitespace().collect();
             let numbers: HashSet<&str> = split.next().unwrap().split_whitespace().collect();
             Game {
@@ -1,30 +1,368 @@
 use itertools::Itertools;
-use std::ops::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+};
 
 #[derive(Debug)]
 pub struct Input {
     seeds: Vec<isize>,
-    maps: Maps,
+    almanac: Almanac,
 }
 
-#[derive(Debug)]
-struct Maps {
-    s2s: Vec<Map>,
-    s2f: Vec<Map>,
-    f2w: Vec<Map>,
-    w2l: Vec<Map>,
-    l2t: Vec<Map>,
-    t2h: Vec<Map>,
-    h2l: Vec<Map>,
+/// `X-to-Y map` sections keyed by their source category
+#[derive(Debug, Default)]
+pub struct Almanac {
+    maps: HashMap<String, (String, RangeMap)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+pub enum AlmanacError {
+    MalformedSection(String),
+    DuplicateCategory(String),
+    MissingCategory(String),
+    NoPath {
+        from: String,
+        to: String,
+    },
+    OverlappingSegments {
+        section: String,
+        overlap: Overlap,
+    },
+    /// the forward chain sends several values to one, or none to some
+    NotInvertible {
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlmanacError::MalformedSection(header) => write!(f, "malformed section: {header}"),
+            AlmanacError::DuplicateCategory(name) => write!(f, "duplicate category: {name}"),
+            AlmanacError::MissingCategory(name) => write!(f, "missing category: {name}"),
+            AlmanacError::NoPath { from, to } => write!(f, "no path from {from} to {to}"),
+            AlmanacError::OverlappingSegments { section, overlap } => {
+                write!(f, "{section} {overlap}")
+            }
+            AlmanacError::NotInvertible { from, to } => {
+                write!(
+                    f,
+                    "{to} to {from} is not one-to-one, so {from} has no single {to}"
+                )
+            }
+        }
+    }
+}
+
+impl Almanac {
+    pub fn parse<'a>(sections: impl Iterator<Item = &'a str>) -> Result<Self, AlmanacError> {
+        let mut almanac = Almanac::default();
+        let mut destinations = HashSet::new();
+
+        for section in sections {
+            let mut lines = section.lines();
+            let header = lines.next().unwrap_or_default();
+            let (source, destination) = header
+                .strip_suffix(" map:")
+                .and_then(|name| name.split_once("-to-"))
+                .ok_or_else(|| AlmanacError::MalformedSection(header.to_string()))?;
+
+            if almanac.maps.contains_key(source) {
+                return Err(AlmanacError::DuplicateCategory(source.to_string()));
+            }
+            if !destinations.insert(destination) {
+                return Err(AlmanacError::DuplicateCategory(destination.to_string()));
+            }
+
+            let maps = parse_maps(lines)
+                .ok_or_else(|| AlmanacError::MalformedSection(header.to_string()))?;
+            let map = RangeMap::new(maps).map_err(|overlap| AlmanacError::OverlappingSegments {
+                section: header.to_string(),
+                overlap,
+            })?;
+            almanac
+                .maps
+                .insert(source.to_string(), (destination.to_string(), map));
+        }
+
+        Ok(almanac)
+    }
+
+    fn contains(&self, category: &str) -> bool {
+        self.maps.contains_key(category)
+            || self
+                .maps
+                .values()
+                .any(|(destination, _)| destination == category)
+    }
+
+    /// follow sections forward from `from`, stopping once `to` is reached
+    fn walk(&self, from: &str, to: &str) -> Option<Vec<&RangeMap>> {
+        let mut chain = Vec::new();
+        let mut category = from;
+
+        while category != to {
+            let (destination, map) = self.maps.get(category)?;
+            chain.push(map);
+            category = destination;
+            // a cycle can't revisit more sections than there are
+            if chain.len() > self.maps.len() {
+                return None;
+            }
+        }
+
+        Some(chain)
+    }
+
+    /// a single map from one category to another, in either direction
+    pub fn path(&self, from: &str, to: &str) -> Result<RangeMap, AlmanacError> {
+        for category in [from, to] {
+            if !self.contains(category) {
+                return Err(AlmanacError::MissingCategory(category.to_string()));
+            }
+        }
+
+        let compose = |chain: Vec<&RangeMap>| {
+            chain
+                .into_iter()
+                .fold(RangeMap::default(), |chain, map| chain.then(map))
+        };
+        if let Some(chain) = self.walk(from, to) {
+            Ok(compose(chain))
+        } else if let Some(chain) = self.walk(to, from) {
+            compose(chain)
+                .invert()
+                .ok_or_else(|| AlmanacError::NotInvertible {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+        } else {
+            Err(AlmanacError::NoPath {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Map {
     offset: isize,
     source: Range<isize>,
-    // source + offset
-    destination: isize,
-    length: isize,
+}
+
+impl Map {
+    /// `length` values from `source` on sent to `destination` on, as in an almanac line
+    pub fn new(destination: isize, source: isize, length: isize) -> Self {
+        Map {
+            offset: destination - source,
+            source: source..source + length,
+        }
+    }
+}
+
+/// Two segments of a map claiming the same values for different destinations
+#[derive(Debug, PartialEq)]
+pub struct Overlap {
+    pub first: Range<isize>,
+    pub second: Range<isize>,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "segments {:?} and {:?} overlap", self.first, self.second)
+    }
+}
+
+/// Piecewise-linear mapping, identity anywhere not covered by a segment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RangeMap {
+    // sorted by start and non-overlapping
+    segments: Vec<Map>,
+}
+
+impl RangeMap {
+    /// segments sharing an offset are merged, any other overlap is an error
+    pub fn new(maps: impl IntoIterator<Item = Map>) -> Result<Self, Overlap> {
+        let segments = maps
+            .into_iter()
+            .filter(|map| !map.source.is_empty())
+            .sorted_by_key(|map| map.source.start)
+            .coalesce(|a, b| {
+                if b.source.start <= a.source.end && a.offset == b.offset {
+                    Ok(Map {
+                        offset: a.offset,
+                        source: a.source.start..a.source.end.max(b.source.end),
+                    })
+                } else {
+                    Err((a, b))
+                }
+            })
+            .collect_vec();
+
+        if let Some((a, b)) = segments
+            .iter()
+            .tuple_windows()
+            .find(|(a, b)| b.source.start < a.source.end)
+        {
+            return Err(Overlap {
+                first: a.source.clone(),
+                second: b.source.clone(),
+            });
+        }
+
+        Ok(RangeMap {
+            segments: segments.into_iter().filter(|map| map.offset != 0).collect(),
+        })
+    }
+
+    pub fn get(&self, value: isize) -> isize {
+        let i = self.segments.partition_point(|map| map.source.end <= value);
+        match self.segments.get(i) {
+            Some(map) if map.source.contains(&value) => value + map.offset,
+            _ => value,
+        }
+    }
+
+    /// cut a range on segment boundaries, pairing each piece with its offset
+    pub fn split(&self, range: Range<isize>) -> Vec<(Range<isize>, isize)> {
+        let mut pieces = Vec::new();
+        let mut start = range.start;
+        let first = self.segments.partition_point(|map| map.source.end <= start);
+
+        for map in &self.segments[first..] {
+            if start >= range.end || map.source.start >= range.end {
+                break;
+            }
+            if map.source.start > start {
+                pieces.push((start..map.source.start, 0));
+                start = map.source.start;
+            }
+            let end = map.source.end.min(range.end);
+            pieces.push((start..end, map.offset));
+            start = end;
+        }
+        if start < range.end {
+            pieces.push((start..range.end, 0));
+        }
+
+        pieces
+    }
+
+    /// the inverse map, only when every value has exactly one preimage
+    pub fn invert(&self) -> Option<RangeMap> {
+        // outside the segments is identity, so their images must tile their sources exactly
+        let sources: IntervalSet = self.segments.iter().map(|map| map.source.clone()).collect();
+        let inverse = self
+            .segments
+            .iter()
+            .map(|map| Map {
+                offset: -map.offset,
+                source: map.source.start + map.offset..map.source.end + map.offset,
+            })
+            .collect_vec();
+        let images: IntervalSet = inverse.iter().map(|map| map.source.clone()).collect();
+
+        if images != sources || images.len() != sources.len() {
+            return None;
+        }
+        RangeMap::new(inverse).ok()
+    }
+
+    pub fn apply(&self, set: &IntervalSet) -> IntervalSet {
+        set.ranges
+            .iter()
+            .flat_map(|range| self.split(range.clone()))
+            .map(|(range, offset)| range.start + offset..range.end + offset)
+            .collect()
+    }
+
+    /// compose into a single map that applies `self` and then `next`
+    pub fn then(&self, next: &RangeMap) -> RangeMap {
+        let segments = self
+            .split(isize::MIN..isize::MAX)
+            .into_iter()
+            .flat_map(|(range, offset)| {
+                next.split(range.start + offset..range.end + offset)
+                    .into_iter()
+                    .map(move |(piece, next_offset)| Map {
+                        offset: offset + next_offset,
+                        source: piece.start - offset..piece.end - offset,
+                    })
+            })
+            .filter(|map| map.offset != 0)
+            .coalesce(|a, b| {
+                if a.source.end == b.source.start && a.offset == b.offset {
+                    Ok(Map {
+                        offset: a.offset,
+                        source: a.source.start..b.source.end,
+                    })
+                } else {
+                    Err((a, b))
+                }
+            })
+            .collect();
+
+        RangeMap { segments }
+    }
+}
+
+/// Sorted, merged set of half-open ranges
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntervalSet {
+    ranges: Vec<Range<isize>>,
+}
+
+impl FromIterator<Range<isize>> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Range<isize>>>(iter: T) -> Self {
+        let ranges = iter
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .sorted_by_key(|range| range.start)
+            .coalesce(|a, b| {
+                if b.start <= a.end {
+                    Ok(a.start..a.end.max(b.end))
+                } else {
+                    Err((a, b))
+                }
+            })
+            .collect();
+
+        IntervalSet { ranges }
+    }
+}
+
+impl IntervalSet {
+    #[allow(dead_code)]
+    pub fn ranges(&self) -> &[Range<isize>] {
+        &self.ranges
+    }
+
+    pub fn min(&self) -> Option<isize> {
+        self.ranges.first().map(|range| range.start)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, value: isize) -> bool {
+        let i = self.ranges.partition_point(|range| range.end <= value);
+        self.ranges
+            .get(i)
+            .is_some_and(|range| range.contains(&value))
+    }
+
+    /// total number of values covered
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
 }
 
 pub fn generator(input: &str) -> Input {
@@ -34,126 +372,51 @@ pub fn generator(input: &str) -> Input {
             .next()
             .unwrap()
             .split(':')
-            .last()
+            .next_back()
             .unwrap()
             .split_whitespace()
             .map(|seed| seed.parse::<isize>().unwrap())
             .collect_vec(),
-        maps: Maps {
-            s2s: parse_map(sections.next().unwrap().lines().skip(1)),
-            s2f: parse_map(sections.next().unwrap().lines().skip(1)),
-            f2w: parse_map(sections.next().unwrap().lines().skip(1)),
-            w2l: parse_map(sections.next().unwrap().lines().skip(1)),
-            l2t: parse_map(sections.next().unwrap().lines().skip(1)),
-            t2h: parse_map(sections.next().unwrap().lines().skip(1)),
-            h2l: parse_map(sections.next().unwrap().lines().skip(1)),
-        },
+        almanac: Almanac::parse(sections).unwrap_or_else(|err| panic!("{err}")),
     }
 }
 
-fn parse_map<'a>(input: impl Iterator<Item = &'a str>) -> Vec<Map> {
+fn parse_maps<'a>(input: impl Iterator<Item = &'a str>) -> Option<Vec<Map>> {
     input
         .map(|line| {
             // destination, source, length
             let items: Vec<isize> = line
                 .split_whitespace()
-                .map(|each| each.parse::<isize>().unwrap())
-                .collect();
-            Map {
-                offset: items[0] - items[1],
-                source: items[1]..items[1] + items[2],
-                destination: items[0],
-                length: items[2],
+                .map(|each| each.parse::<isize>().ok())
+                .collect::<Option<_>>()?;
+            match items[..] {
+                [destination, source, length] => Some(Map::new(destination, source, length)),
+                _ => None,
             }
         })
         .collect()
 }
 
 pub fn part1(input: &Input) -> isize {
+    let locations = input.almanac.path("seed", "location").unwrap();
     input
         .seeds
         .iter()
-        .map(|seed| get_location(seed, &input.maps))
+        .map(|seed| locations.get(*seed))
         .min()
         .unwrap()
 }
 
 pub fn part2(input: &Input) -> isize {
-    // search backwards by trying every location starting at 0
-    let seeds = input
+    // push whole seed ranges through a single composed map
+    let seeds: IntervalSet = input
         .seeds
         .chunks(2)
         .map(|chunk| chunk[0]..chunk[0] + chunk[1])
-        .collect_vec();
+        .collect();
 
-    let mut location: isize = 0;
-    loop {
-        let seed = go_backwards(&location, &input.maps);
-        if seeds.iter().any(|range| range.contains(&seed)) {
-            return location;
-        }
-        location += 1;
-    }
-
-    // input
-    //     .seeds
-    //     .chunks(2)
-    //     .flat_map(|chunk| chunk[0]..chunk[0] + chunk[1])
-    //     .map(|seed| get_location(&seed, &input.maps))
-    //     .min()
-    //     .unwrap()
-}
-
-fn get_dest(find_me: isize, map: &[Map]) -> isize {
-    if let Some(found) = map.iter().find(|each| each.source.contains(&find_me)) {
-        found.destination + find_me - found.source.start
-    } else {
-        find_me
-    }
-}
-
-fn get_location(seed: &isize, maps: &Maps) -> isize {
-    // format this better
-    get_dest(
-        get_dest(
-            get_dest(
-                get_dest(
-                    get_dest(get_dest(get_dest(*seed, &maps.s2s), &maps.s2f), &maps.f2w),
-                    &maps.w2l,
-                ),
-                &maps.l2t,
-            ),
-            &maps.t2h,
-        ),
-        &maps.h2l,
-    )
-}
-
-fn go_backwards(location: &isize, maps: &Maps) -> isize {
-    get_src(
-        get_src(
-            get_src(
-                get_src(
-                    get_src(get_src(get_src(*location, &maps.h2l), &maps.t2h), &maps.l2t),
-                    &maps.w2l,
-                ),
-                &maps.f2w,
-            ),
-            &maps.s2f,
-        ),
-        &maps.s2s,
-    )
-}
-
-fn get_src(find_me: isize, map: &[Map]) -> isize {
-    if let Some(found) = map
-        .iter()
-        .find(|each| (each.destination..each.destination + each.length).contains(&find_me))
-    {
-        find_me - found.offset
-    } else {
-        find_me
-    }
+    let locations = input.almanac.path("seed", "location").unwrap();
+    locations.apply(&seeds).min().unwrap()
 }
 
 #[cfg(test)]
@@ -203,4 +466,77 @@ humidity-to-location map:
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 46);
     }
+
+    #[test]
+    fn test_range_map() {
+        let a = RangeMap::new([Map::new(10, 0, 10)]).unwrap();
+        let b = RangeMap::new([Map::new(10, 15, 15)]).unwrap();
+        let chain = a.then(&b);
+
+        for value in -5..40 {
+            assert_eq!(chain.get(value), b.get(a.get(value)));
+        }
+
+        let seeds: IntervalSet = [0..10, 8..20].into_iter().collect();
+        assert_eq!(seeds.len(), 20);
+
+        let locations = chain.apply(&seeds);
+        assert_eq!(locations.ranges(), vec![10..15]);
+        assert!(!locations.contains(15));
+
+        // touching or overlapping segments with one offset merge, others clash
+        assert_eq!(
+            RangeMap::new([Map::new(10, 0, 5), Map::new(13, 3, 5)]),
+            RangeMap::new([Map::new(10, 0, 8)])
+        );
+        assert_eq!(
+            RangeMap::new([Map::new(50, 0, 10), Map::new(0, 5, 10), Map::new(99, 20, 1)]),
+            Err(Overlap {
+                first: 0..10,
+                second: 5..15
+            })
+        );
+        let overlapping = Almanac::parse(["a-to-b map:\n1 0 3\n5 2 2"].into_iter());
+        assert_eq!(
+            overlapping.unwrap_err().to_string(),
+            "a-to-b map: segments 0..3 and 2..4 overlap"
+        );
+    }
+
+    #[test]
+    fn test_almanac() {
+        let input = generator(SAMPLE);
+        let almanac = &input.almanac;
+
+        let humidity = almanac.path("seed", "humidity").unwrap();
+        assert_eq!(humidity.get(79), 78);
+        let soil = almanac.path("location", "soil").unwrap();
+        assert_eq!(soil.get(82), 81);
+
+        assert_eq!(
+            almanac.path("seed", "mana").unwrap_err(),
+            AlmanacError::MissingCategory(String::from("mana"))
+        );
+
+        // 0..5 moves onto 10..15, so 10..15 has two preimages and 0..5 none
+        let almanac = Almanac::parse(["a-to-b map:\n10 0 5"].into_iter()).unwrap();
+        assert_eq!(almanac.path("a", "b").unwrap().get(3), 13);
+        assert_eq!(
+            almanac.path("b", "a").unwrap_err(),
+            AlmanacError::NotInvertible {
+                from: String::from("b"),
+                to: String::from("a")
+            }
+        );
+        // a swap of two blocks is a bijection
+        let almanac = Almanac::parse(["a-to-b map:\n10 0 5\n0 10 5"].into_iter()).unwrap();
+        let back = almanac.path("b", "a").unwrap();
+        assert_eq!((back.get(13), back.get(3), back.get(20)), (3, 13, 20));
+
+        let duplicate = Almanac::parse(["a-to-b map:\n1 2 3", "a-to-c map:\n1 2 3"].into_iter());
+        assert_eq!(
+            duplicate.unwrap_err(),
+            AlmanacError::DuplicateCategory(String::from("a"))
+        );
+    }
 }
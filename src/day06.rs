@@ -1,11 +1,13 @@
 use itertools::Itertools;
+use num_integer::{Integer, Roots};
+use std::ops::RangeInclusive;
 
-type Input = Vec<Vec<usize>>;
+type Input = Vec<Vec<u64>>;
 
 #[derive(Debug)]
-pub struct Race {
-    time: usize,
-    distance: usize,
+pub struct Race<T> {
+    time: T,
+    distance: T,
 }
 
 pub fn generator(input: &str) -> Input {
@@ -13,16 +15,16 @@ pub fn generator(input: &str) -> Input {
         .lines()
         .map(|line| {
             line.split(':')
-                .last()
+                .next_back()
                 .unwrap()
                 .split_whitespace()
-                .map(|num| num.parse::<usize>().unwrap())
+                .map(|num| num.parse::<u64>().unwrap())
                 .collect_vec()
         })
         .collect_vec()
 }
 
-fn flip_arrays(lines: &Input) -> Vec<Race> {
+fn flip_arrays(lines: &Input) -> Vec<Race<u64>> {
     let mut lines = lines.iter();
     lines
         .next()
@@ -36,14 +38,14 @@ fn flip_arrays(lines: &Input) -> Vec<Race> {
         .collect_vec()
 }
 
-pub fn part1(input: &Input) -> usize {
+pub fn part1(input: &Input) -> u64 {
     flip_arrays(input)
         .iter()
         .map(winning_combinations)
         .product()
 }
 
-pub fn part2(input: &Input) -> usize {
+pub fn part2(input: &Input) -> u128 {
     let race = Race {
         time: combine_numbers(&input[0]),
         distance: combine_numbers(&input[1]),
@@ -51,24 +53,53 @@ pub fn part2(input: &Input) -> usize {
     winning_combinations(&race)
 }
 
-fn combine_numbers(numbers: &[usize]) -> usize {
-    numbers
-        .iter()
-        .map(|x| x.to_string())
-        .join("")
-        .parse::<usize>()
-        .unwrap()
+/// concatenate the digits of each number, ignoring the kerning
+fn combine_numbers<T: Integer + Clone + From<u64>>(numbers: &[u64]) -> T {
+    numbers.iter().fold(T::zero(), |combined, num| {
+        let digits = num.checked_ilog10().unwrap_or(0) as usize + 1;
+        combined * num_traits::pow(T::from(10), digits) + T::from(*num)
+    })
+}
+
+fn wins<T: Integer + Clone>(race: &Race<T>, charge: &T) -> bool {
+    charge.clone() * (race.time.clone() - charge.clone()) > race.distance
+}
+
+/// charge times that beat the record, from the roots of charge * (time - charge) = distance
+pub fn winning_interval<T: Integer + Roots + Clone>(race: &Race<T>) -> Option<RangeInclusive<T>> {
+    let two = T::one() + T::one();
+    let four = two.clone() + two.clone();
+    let time_squared = race.time.clone() * race.time.clone();
+    let record = four * race.distance.clone();
+    if time_squared <= record {
+        return None;
+    }
+
+    // floor((time - isqrt(discriminant)) / 2) is at most one short of the first win
+    let root = (time_squared - record).sqrt();
+    let mut first = (race.time.clone() - root) / two;
+    if !wins(race, &first) {
+        first = first + T::one();
+    }
+    if first > race.time || !wins(race, &first) {
+        return None;
+    }
+
+    let last = race.time.clone() - first.clone();
+    Some(first..=last)
 }
 
-fn winning_combinations(race: &Race) -> usize {
-    (1..race.time - 1)
-        .filter(|charge| (race.time - charge) * charge > race.distance)
-        .count()
+fn winning_combinations<T: Integer + Roots + Clone>(race: &Race<T>) -> T {
+    winning_interval(race).map_or_else(T::zero, |interval| {
+        let (first, last) = interval.into_inner();
+        last - first + T::one()
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_bigint::BigUint;
 
     const SAMPLE: &str = "Time:      7  15   30
 Distance:  9  40  200";
@@ -82,4 +113,44 @@ Distance:  9  40  200";
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 71503);
     }
+
+    #[test]
+    fn test_winning_interval() {
+        let race = Race {
+            time: 30u128,
+            distance: 200,
+        };
+        // the roots land exactly on 10 and 20, which only tie the record
+        assert_eq!(winning_interval(&race), Some(11..=19));
+        assert_eq!(
+            winning_interval(&Race {
+                time: 2,
+                distance: 1
+            }),
+            None
+        );
+
+        let race: Race<BigUint> = Race {
+            time: combine_numbers(&[71530; 4]),
+            distance: combine_numbers(&[940200; 4]),
+        };
+        let interval = winning_interval(&race).unwrap();
+        assert!(wins(&race, interval.start()) && wins(&race, interval.end()));
+        assert!(!wins(&race, &(interval.start() - 1u32)));
+        assert!(!wins(&race, &(interval.end() + 1u32)));
+    }
+
+    #[test]
+    fn test_combine_numbers() {
+        assert_eq!(combine_numbers::<u64>(&[7, 15, 30]), 71530);
+        // 10^20 is past u64::MAX, so the power has to be taken in the target type
+        assert_eq!(
+            combine_numbers::<u128>(&[1, u64::MAX]),
+            118446744073709551615
+        );
+        assert_eq!(
+            combine_numbers::<BigUint>(&[u64::MAX; 2]).to_string(),
+            u64::MAX.to_string().repeat(2)
+        );
+    }
 }
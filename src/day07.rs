@@ -1,16 +1,135 @@
 use itertools::Itertools;
-use std::{cmp::Ordering, collections::HashMap};
+use std::collections::HashSet;
 
 type Input<'a> = Vec<(&'a str, usize)>;
 
-#[derive(Debug)]
-pub struct Hand<'a> {
-    hand: &'a str,
-    bid: usize,
-    hand_type: HandType,
+/// Everything needed to rank hands of a Camel Cards style game
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    /// cards from weakest to strongest, used to break ties
+    pub order: Vec<char>,
+    pub hand_size: usize,
+    /// cards that can stand in for any other card
+    pub wild: Vec<char>,
+    /// card count patterns from weakest to strongest, largest count first
+    pub categories: Vec<Vec<usize>>,
 }
 
-pub fn generator(input: &str) -> Input {
+const CAMEL_CATEGORIES: [&[usize]; 7] = [
+    &[1, 1, 1, 1, 1], // high card
+    &[2, 1, 1, 1],    // one pair
+    &[2, 2, 1],       // two pair
+    &[3, 1, 1],       // three of a kind
+    &[3, 2],          // full house
+    &[4, 1],          // four of a kind
+    &[5],             // five of a kind
+];
+
+impl RuleSet {
+    pub fn new(order: &str, hand_size: usize, wild: &str, categories: &[&[usize]]) -> Self {
+        RuleSet {
+            order: order.chars().collect(),
+            hand_size,
+            wild: wild.chars().collect(),
+            categories: categories.iter().map(|pattern| pattern.to_vec()).collect(),
+        }
+    }
+
+    pub fn camel_cards() -> Self {
+        RuleSet::new("23456789TJQKA", 5, "", &CAMEL_CATEGORIES)
+    }
+
+    pub fn jokers_wild() -> Self {
+        RuleSet::new("J23456789TQKA", 5, "J", &CAMEL_CATEGORIES)
+    }
+
+    pub fn card_value(&self, card: char) -> usize {
+        self.order
+            .iter()
+            .position(|each| each == &card)
+            .unwrap_or_else(|| panic!("Unknown card value {card}"))
+    }
+
+    /// category followed by each card's value, packed so that integer order is hand order
+    pub fn sort_key(&self, hand: &str) -> u128 {
+        // bits to hold any index below `len`
+        let bits = |len: usize| usize::BITS - len.saturating_sub(1).leading_zeros();
+        let (category_bits, card_bits) = (bits(self.categories.len()), bits(self.order.len()));
+        if category_bits as usize + card_bits as usize * self.hand_size > 128 {
+            panic!("hands are too large to pack into a sort key");
+        }
+
+        hand.chars().fold(self.category(hand) as u128, |key, card| {
+            key << card_bits | self.card_value(card) as u128
+        })
+    }
+
+    /// index into `categories` of the best hand the wild cards can make
+    pub fn category(&self, hand: &str) -> usize {
+        if hand.chars().count() != self.hand_size {
+            panic!("{hand} is not a {} card hand", self.hand_size);
+        }
+
+        let counts = hand
+            .chars()
+            .filter(|card| !self.wild.contains(card))
+            .counts()
+            .into_values()
+            .collect_vec();
+        let wilds = self.hand_size - counts.iter().sum::<usize>();
+
+        self.wild_assignments(counts, wilds)
+            .iter()
+            .filter_map(|counts| self.best_category(counts))
+            .max()
+            .unwrap_or_else(|| panic!("{hand} matched no hand category"))
+    }
+
+    /// every count pattern reachable by adding the wild cards to the hand
+    fn wild_assignments(&self, counts: Vec<usize>, wilds: usize) -> HashSet<Vec<usize>> {
+        let mut assignments = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut queue = vec![(counts, wilds)];
+
+        while let Some((mut counts, wilds)) = queue.pop() {
+            counts.sort_unstable_by(|a, b| b.cmp(a));
+            if !seen.insert((counts.clone(), wilds)) {
+                continue;
+            }
+            if wilds == 0 {
+                assignments.insert(counts);
+                continue;
+            }
+
+            for i in 0..counts.len() {
+                let mut next = counts.clone();
+                next[i] += 1;
+                queue.push((next, wilds - 1));
+            }
+            // or become a card not already in the hand
+            if counts.len() < self.order.len() {
+                let mut next = counts;
+                next.push(1);
+                queue.push((next, wilds - 1));
+            }
+        }
+
+        assignments
+    }
+
+    /// strongest category whose pattern the sorted counts cover
+    fn best_category(&self, counts: &[usize]) -> Option<usize> {
+        self.categories.iter().rposition(|pattern| {
+            pattern.len() <= counts.len()
+                && pattern
+                    .iter()
+                    .zip(counts)
+                    .all(|(needed, count)| count >= needed)
+        })
+    }
+}
+
+pub fn generator(input: &str) -> Input<'_> {
     input
         .lines()
         .map(|line| {
@@ -20,124 +139,30 @@ pub fn generator(input: &str) -> Input {
         .collect_vec()
 }
 
-fn main(input: &Input, joker: bool) -> usize {
+fn main(input: &Input, rules: &RuleSet) -> usize {
     input
         .iter()
-        .map(|each| Hand {
-            hand: each.0,
-            bid: each.1,
-            hand_type: hand_type(each.0, joker),
-        })
-        .sorted_by(|a, b| {
-            if a.hand_type == b.hand_type {
-                for card in 0..5 {
-                    let a_card = card_value(a.hand.chars().nth(card).unwrap(), joker);
-                    let b_card = card_value(b.hand.chars().nth(card).unwrap(), joker);
-                    if a_card != b_card {
-                        return a_card.cmp(&b_card);
-                    }
-                }
-                Ordering::Equal
-            } else {
-                a.hand_type.cmp(&b.hand_type)
-            }
-        })
+        .map(|(hand, bid)| (rules.sort_key(hand), bid))
+        .sorted_unstable_by_key(|(key, _)| *key)
         .enumerate()
-        .map(|(i, hand)| hand.bid * (i + 1))
+        .map(|(i, (_, bid))| bid * (i + 1))
         .sum()
 }
 
 pub fn part1(input: &Input) -> usize {
-    main(input, false)
+    main(input, &RuleSet::camel_cards())
 }
 
 pub fn part2(input: &Input) -> usize {
-    main(input, true)
-}
-
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-enum HandType {
-    HighCard,
-    OnePair,
-    TwoPair,
-    ThreeKind,
-    FullHouse,
-    FourKind,
-    FiveKind,
-}
-
-fn hand_type(hand: &str, joker: bool) -> HandType {
-    let counts = hand.chars().counts();
-
-    if joker && hand.contains('J') {
-        let joker = determine_joker(&counts);
-        let hand = hand.replace('J', &joker.to_string());
-        // send it back through with the replaced card
-        return hand_type(&hand, false);
-    }
-
-    if hand.chars().all_equal() {
-        return HandType::FiveKind;
-    }
-    if counts.values().any(|count| count == &4) {
-        return HandType::FourKind;
-    }
-    if counts.values().any(|count| count == &3) && counts.values().any(|count| count == &2) {
-        return HandType::FullHouse;
-    }
-    if counts.values().any(|count| count == &3) {
-        return HandType::ThreeKind;
-    }
-    if counts.values().filter(|count| count == &&2).count() == 2 {
-        return HandType::TwoPair;
-    }
-    if counts.values().any(|count| count == &2) {
-        return HandType::OnePair;
-    }
-    if hand.chars().all_unique() {
-        return HandType::HighCard;
-    }
-    panic!("Unknown hand type")
-}
-
-fn card_value(hand: char, joker: bool) -> u32 {
-    hand.to_digit(10).unwrap_or_else(|| match hand {
-        'T' => 10,
-        'J' => {
-            if joker {
-                1
-            } else {
-                11
-            }
-        }
-        'Q' => 12,
-        'K' => 13,
-        'A' => 14,
-        _ => panic!("Unknown card value"),
-    })
-}
-
-fn determine_joker(counts: &HashMap<char, usize>) -> char {
-    let without_j: Vec<(&char, &usize)> = counts.iter().filter(|each| each.0 != &'J').collect();
-    // two pairs need to check for the higher value
-    let highest = if without_j.iter().filter(|each| each.1 == &2).count() == 2 {
-        if card_value(*without_j[0].0, true) > card_value(*without_j[1].0, true) {
-            without_j[0]
-        } else {
-            without_j[1]
-        }
-    } else if !without_j.is_empty() {
-        *without_j.iter().max_by(|a, b| a.1.cmp(b.1)).unwrap()
-    } else {
-        (&'J', &5)
-    };
-
-    *highest.0
+    main(input, &RuleSet::jokers_wild())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bench;
+    use rand::seq::SliceRandom;
+    use std::cmp::Ordering;
 
     const SAMPLE: &str = "32T3K 765
 T55J5 684
@@ -154,4 +179,117 @@ QQQJA 483";
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 5905);
     }
+
+    #[test]
+    fn test_rule_set() {
+        let jokers = RuleSet::jokers_wild();
+        assert_eq!(jokers.category("JJJJJ"), 6);
+        assert_eq!(jokers.category("KTJJT"), 5);
+        assert_eq!(jokers.category("2345J"), 1);
+
+        let two_wild = RuleSet::new("J23456789TQKA", 5, "J2", &CAMEL_CATEGORIES);
+        assert_eq!(two_wild.category("J2345"), 3);
+        assert_eq!(two_wild.category("J2K3K"), 5);
+
+        // six cards with two triples above four of a kind
+        let six_cards = RuleSet::new(
+            "J23456789TQKA",
+            6,
+            "J",
+            &[
+                &[1],
+                &[2],
+                &[2, 2],
+                &[3],
+                &[3, 2],
+                &[3, 3],
+                &[4],
+                &[5],
+                &[6],
+            ],
+        );
+        assert_eq!(six_cards.category("KKQQJ2"), 4);
+        assert_eq!(six_cards.category("KKQQJJ"), 6);
+        assert_eq!(six_cards.category("KKKQJ2"), 6);
+    }
+
+    /// two card kinds with one category per largest count, 7 bits of categories
+    fn long_hands(hand_size: usize) -> RuleSet {
+        let categories = (1..=126).map(|count| vec![count]).collect_vec();
+        RuleSet::new(
+            "AB",
+            hand_size,
+            "",
+            &categories.iter().map(Vec::as_slice).collect_vec(),
+        )
+    }
+
+    #[test]
+    fn test_sort_key_capacity() {
+        // 7 category bits and 121 card bits fill the key exactly
+        let rules = long_hands(121);
+        let all_a = "A".repeat(121);
+        let one_b = "A".repeat(120) + "B";
+        assert_eq!(rules.category(&all_a), 120);
+        assert!(rules.sort_key(&all_a) > rules.sort_key(&one_b));
+    }
+
+    #[test]
+    #[should_panic(expected = "too large to pack")]
+    fn test_sort_key_overflow() {
+        // one card bit each fits 126 cards in 127 bits, but not the 7 category bits too
+        long_hands(126).sort_key(&"A".repeat(126));
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_sort_keys() {
+        let rules = RuleSet::jokers_wild();
+        let mut rng = bench::rng(2023);
+        let hands = (0..1_000_000)
+            .map(|_| {
+                (0..5)
+                    .map(|_| *rules.order.choose(&mut rng).unwrap())
+                    .collect::<String>()
+            })
+            .collect_vec();
+
+        let (scanned, keyed) = bench::compare(
+            "per-compare scanning to packed keys",
+            || {
+                hands
+                    .iter()
+                    .map(|hand| (hand, rules.category(hand)))
+                    .sorted_by(|a, b| {
+                        if a.1 == b.1 {
+                            for card in 0..5 {
+                                let a_card = rules.card_value(a.0.chars().nth(card).unwrap());
+                                let b_card = rules.card_value(b.0.chars().nth(card).unwrap());
+                                if a_card != b_card {
+                                    return a_card.cmp(&b_card);
+                                }
+                            }
+                            Ordering::Equal
+                        } else {
+                            a.1.cmp(&b.1)
+                        }
+                    })
+                    .map(|(hand, _)| hand)
+                    .collect_vec()
+            },
+            || {
+                hands
+                    .iter()
+                    .map(|hand| (rules.sort_key(hand), hand))
+                    .sorted_unstable_by_key(|(key, _)| *key)
+                    .map(|(_, hand)| hand)
+                    .collect_vec()
+            },
+        );
+
+        assert!(scanned
+            .iter()
+            .zip(&keyed)
+            .all(|(a, b)| rules.sort_key(a) == rules.sort_key(b)));
+    }
 }
@@ -1,9 +1,68 @@
 use itertools::Itertools;
+use num_integer::{ExtendedGcd, Integer};
 use regex::Regex;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
+
+/// Nodes interned to dense ids, with (left, right) exits indexed by id
+#[derive(Debug, Default)]
+pub struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    exits: Vec<[u32; 2]>,
+}
+
+impl Network {
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// flags indexed by id, set for nodes whose names match
+    pub fn matching(&self, predicate: impl Fn(&str) -> bool) -> Vec<bool> {
+        self.names.iter().map(|name| predicate(name)).collect()
+    }
+
+    pub fn next(&self, id: u32, instruction: usize) -> u32 {
+        self.exits[id as usize][instruction]
+    }
+
+    /// Graphviz export with start nodes in green and end nodes in red
+    #[allow(dead_code)]
+    pub fn to_dot(&self, is_start: impl Fn(&str) -> bool, is_end: impl Fn(&str) -> bool) -> String {
+        let mut dot = String::from("digraph network {\n");
+
+        for name in &self.names {
+            if is_start(name) {
+                dot += &format!("    \"{name}\" [style=filled, fillcolor=palegreen];\n");
+            } else if is_end(name) {
+                dot += &format!("    \"{name}\" [style=filled, fillcolor=lightcoral];\n");
+            }
+        }
+        for (name, [left, right]) in self.names.iter().zip(&self.exits) {
+            if left == right {
+                dot += &format!("    \"{name}\" -> \"{}\" [label=LR];\n", self.name(*left));
+            } else {
+                dot += &format!("    \"{name}\" -> \"{}\" [label=L];\n", self.name(*left));
+                dot += &format!("    \"{name}\" -> \"{}\" [label=R];\n", self.name(*right));
+            }
+        }
+
+        dot + "}\n"
+    }
+}
 
-type Choices = Vec<String>;
-type Network = HashMap<String, Choices>;
 #[derive(Debug)]
 pub struct Input {
     instructions: Vec<usize>,
@@ -23,14 +82,34 @@ pub fn generator(input: &str) -> Input {
     lines.next();
 
     let re = Regex::new(r"[A-Z0-9]{3}").unwrap();
-    let mut network: Network = HashMap::new();
-    lines.for_each(|line| {
-        let mut matches = re.find_iter(line);
-        network.insert(
-            String::from(matches.next().unwrap().as_str()),
-            matches.map(|each| String::from(each.as_str())).collect(),
-        );
-    });
+    let nodes = lines
+        .map(|line| {
+            let (node, left, right) = re
+                .find_iter(line)
+                .map(|each| each.as_str())
+                .collect_tuple()
+                .unwrap();
+            (node, [left, right])
+        })
+        .collect_vec();
+
+    let mut network = Network::default();
+    for (node, _) in &nodes {
+        network
+            .ids
+            .insert(node.to_string(), network.names.len() as u32);
+        network.names.push(node.to_string());
+    }
+    network.exits = nodes
+        .iter()
+        .map(|(_, exits)| {
+            exits.map(|name| {
+                network
+                    .id(name)
+                    .unwrap_or_else(|| panic!("{name} is not in the network"))
+            })
+        })
+        .collect();
 
     Input {
         instructions,
@@ -39,68 +118,220 @@ pub fn generator(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> usize {
-    let mut count = 0;
-    let mut node = String::from("AAA");
+    let network = &input.network;
+    let start = network.id("AAA").unwrap();
+    let ends = network.matching(|name| name == "ZZZ");
 
-    while node != "ZZZ" {
-        follow_map(input, &mut count, &mut node);
-    }
+    find_cycle(input, start, &ends)
+        .first_hit()
+        .unwrap_or_else(|| panic!("ZZZ is never reached"))
+}
 
-    count
+pub fn part2(input: &Input) -> u128 {
+    ghost_walk(input).unwrap_or_else(|err| panic!("{err}"))
 }
 
-pub fn part2(input: &Input) -> usize {
-    let nodes = input
-        .network
-        .keys()
-        .filter(|each| each.ends_with('A'))
-        .cloned()
-        .collect_vec();
+/// Where a walk settles into a loop over (node, instruction index) states
+#[derive(Debug, PartialEq)]
+pub struct Cycle {
+    /// steps taken before the loop starts
+    pub mu: usize,
+    /// length of the loop
+    pub lambda: usize,
+    /// steps on an end node before the loop starts
+    pub prefix_hits: Vec<usize>,
+    /// steps on an end node during the first lap, repeating every `lambda`
+    pub cycle_hits: Vec<usize>,
+}
 
-    let counts = nodes
-        .iter()
-        .map(|node| {
-            let mut count = 0;
-            let mut node = node.clone();
+impl Cycle {
+    fn first_hit(&self) -> Option<usize> {
+        self.prefix_hits
+            .first()
+            .or(self.cycle_hits.first())
+            .copied()
+    }
 
-            while !node.ends_with('Z') {
-                follow_map(input, &mut count, &mut node);
-            }
+    fn hits(&self, step: usize) -> bool {
+        if step < self.mu {
+            self.prefix_hits.contains(&step)
+        } else {
+            let lap = (step - self.mu) % self.lambda + self.mu;
+            self.cycle_hits.contains(&lap)
+        }
+    }
+}
 
-            count
-        })
-        .collect_vec();
+/// Why the ghosts never all stand on Z nodes at once
+#[derive(Debug, PartialEq)]
+pub enum Unsatisfiable {
+    /// a ghost with no Z node in its loop, and its last Z step before it if any
+    NoEnd {
+        ghost: String,
+        last_hit: Option<usize>,
+    },
+    /// congruence systems with no common solution, each as (residue, modulus) pairs
+    Systems(Vec<Vec<(u128, u128)>>),
+}
+
+impl fmt::Display for Unsatisfiable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ghosts never finish together, ")?;
+        match self {
+            Unsatisfiable::NoEnd {
+                ghost,
+                last_hit: None,
+            } => write!(f, "{ghost} never reaches a Z node"),
+            Unsatisfiable::NoEnd {
+                ghost,
+                last_hit: Some(step),
+            } => write!(f, "{ghost} last reaches a Z node at step {step}"),
+            Unsatisfiable::Systems(systems) => {
+                let systems = systems.iter().map(|system| {
+                    system
+                        .iter()
+                        .map(|(residue, modulus)| format!("x = {residue} mod {modulus}"))
+                        .join(", ")
+                });
+                write!(f, "unsatisfiable: [{}]", systems.format("; "))
+            }
+        }
+    }
+}
 
-    lcm(&counts)
+/// nodes visited from `start`, forever
+pub fn walk(input: &Input, start: u32) -> impl Iterator<Item = u32> + '_ {
+    let steps = input.instructions.iter().cycle();
+    steps.scan(start, |node, step| {
+        let current = *node;
+        *node = input.network.next(current, *step);
+        Some(current)
+    })
 }
 
-fn follow_map(input: &Input, count: &mut usize, node: &mut String) {
-    let Input {
-        instructions,
-        network,
-    } = input;
+pub fn find_cycle(input: &Input, start: u32, ends: &[bool]) -> Cycle {
+    let length = input.instructions.len();
+    // first step seen at each (node, instruction index)
+    let mut seen = vec![usize::MAX; input.network.len() * length];
+    let mut hits = Vec::new();
+
+    for (step, node) in walk(input, start).enumerate() {
+        let state = node as usize * length + step % length;
+        if seen[state] != usize::MAX {
+            let mu = seen[state];
+            let (prefix_hits, cycle_hits) = hits.into_iter().partition(|hit| *hit < mu);
+            return Cycle {
+                mu,
+                lambda: step - mu,
+                prefix_hits,
+                cycle_hits,
+            };
+        }
+        seen[state] = step;
 
-    for step in instructions {
-        *node = network.get(node).unwrap()[*step].clone();
-        *count += 1;
+        if ends[node as usize] {
+            hits.push(step);
+        }
     }
+
+    unreachable!("walks never end")
 }
 
-// https://github.com/TheAlgorithms/Rust/blob/master/src/math/lcm_of_n_numbers.rs
-fn lcm(nums: &[usize]) -> usize {
-    if nums.len() == 1 {
-        return nums[0];
+/// first step where every ghost stands on a Z node at once, 0 when there are no ghosts
+pub fn ghost_walk(input: &Input) -> Result<u128, Unsatisfiable> {
+    let network = &input.network;
+    let starts = network.matching(|name| name.ends_with('A'));
+    let ends = network.matching(|name| name.ends_with('Z'));
+    let ghosts = (0..network.len() as u32)
+        .filter(|node| starts[*node as usize])
+        .collect_vec();
+    let cycles = ghosts
+        .iter()
+        .map(|node| find_cycle(input, *node, &ends))
+        .collect_vec();
+
+    // before every ghost is looping, check the first ghost's hits directly
+    let looping = cycles.iter().map(|cycle| cycle.mu).max().unwrap_or(0);
+    let Some(first) = cycles.first() else {
+        return Ok(0);
+    };
+    let early = first
+        .prefix_hits
+        .iter()
+        .copied()
+        .chain(
+            first
+                .cycle_hits
+                .iter()
+                .flat_map(|hit| (*hit..looping).step_by(first.lambda)),
+        )
+        .sorted()
+        .find(|step| cycles.iter().all(|cycle| cycle.hits(*step)));
+    if let Some(step) = early {
+        return Ok(step as u128);
     }
-    let a = nums[0];
-    let b = lcm(&nums[1..]);
-    a * b / gcd_of_two_numbers(a, b)
-}
+    if let Some((ghost, cycle)) = ghosts
+        .iter()
+        .zip(&cycles)
+        .find(|(_, cycle)| cycle.cycle_hits.is_empty())
+    {
+        return Err(Unsatisfiable::NoEnd {
+            ghost: network.name(*ghost).to_string(),
+            last_hit: cycle.prefix_hits.last().copied(),
+        });
+    }
+
+    // afterwards each ghost needs step = hit (mod lambda) for one of its hits
+    let mut unsatisfiable = Vec::new();
+    let mut best: Option<u128> = None;
+    for hits in cycles
+        .iter()
+        .map(|cycle| &cycle.cycle_hits)
+        .multi_cartesian_product()
+    {
+        let system = hits
+            .iter()
+            .zip(&cycles)
+            .map(|(hit, cycle)| (**hit as u128 % cycle.lambda as u128, cycle.lambda as u128))
+            .collect_vec();
 
-fn gcd_of_two_numbers(a: usize, b: usize) -> usize {
-    if b == 0 {
-        return a;
+        match crt(&system) {
+            Some((residue, modulus)) => {
+                // smallest solution at or after every ghost is looping
+                let looping = looping as u128;
+                let step = if residue >= looping {
+                    residue
+                } else {
+                    residue + (looping - residue).div_ceil(modulus) * modulus
+                };
+                best = Some(best.map_or(step, |best| best.min(step)));
+            }
+            None => unsatisfiable.push(system),
+        }
     }
-    gcd_of_two_numbers(b, a % b)
+
+    best.ok_or(Unsatisfiable::Systems(unsatisfiable))
+}
+
+/// generalised Chinese Remainder Theorem, moduli need not be coprime
+fn crt(congruences: &[(u128, u128)]) -> Option<(u128, u128)> {
+    congruences.iter().try_fold(
+        (0, 1),
+        |(residue, modulus), &(next_residue, next_modulus)| {
+            let (residue, modulus) = (residue as i128, modulus as i128);
+            let (next_residue, next_modulus) = (next_residue as i128, next_modulus as i128);
+
+            let ExtendedGcd { gcd, x, .. } = modulus.extended_gcd(&next_modulus);
+            if (next_residue - residue) % gcd != 0 {
+                return None;
+            }
+
+            let step = next_modulus / gcd;
+            let k = ((next_residue - residue) / gcd).mod_floor(&step) * x.mod_floor(&step) % step;
+            let lcm = modulus.checked_mul(step).expect("cycle lengths overflowed");
+            Some(((residue + modulus * k).mod_floor(&lcm) as u128, lcm as u128))
+        },
+    )
 }
 
 #[cfg(test)]
@@ -140,8 +371,119 @@ XXX = (XXX, XXX)";
         assert_eq!(part1(&generator(SAMPLE1B)), 6);
     }
 
+    #[test]
+    #[should_panic(expected = "ZZZ is never reached")]
+    fn test_part1_unreachable() {
+        part1(&generator(
+            "L
+
+AAA = (BBB, BBB)
+BBB = (AAA, AAA)
+ZZZ = (ZZZ, ZZZ)",
+        ));
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE2)), 6);
     }
+
+    #[test]
+    fn test_ghost_walk() {
+        // 11A lands on Z at 2, 4, 6... and 22A at 1, 4, 7... so the lcm of 2 and 1 is wrong
+        let input = generator(
+            "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)",
+        );
+        assert_eq!(
+            find_cycle(
+                &input,
+                input.network.id("22A").unwrap(),
+                &input.network.matching(|node| node.ends_with('Z'))
+            ),
+            Cycle {
+                mu: 1,
+                lambda: 3,
+                prefix_hits: vec![],
+                cycle_hits: vec![1],
+            }
+        );
+        assert_eq!(ghost_walk(&input), Ok(4));
+
+        // 33A only lands on Z at odd steps
+        let input = generator(
+            "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11B, 11B)
+33A = (33Z, 33Z)
+33Z = (33B, 33B)
+33B = (33Z, 33Z)",
+        );
+        assert_eq!(
+            ghost_walk(&input),
+            Err(Unsatisfiable::Systems(vec![vec![(0, 2), (1, 2)]]))
+        );
+
+        // 33A passes 33Z once on the way into a loop without one
+        let input = generator(
+            "L
+
+11A = (11Z, 11Z)
+11Z = (11A, 11A)
+33A = (33B, 33B)
+33B = (33Z, 33Z)
+33Z = (33C, 33C)
+33C = (33C, 33C)",
+        );
+        let err = ghost_walk(&input).unwrap_err();
+        assert_eq!(
+            err,
+            Unsatisfiable::NoEnd {
+                ghost: String::from("33A"),
+                last_hit: Some(2)
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "ghosts never finish together, 33A last reaches a Z node at step 2"
+        );
+
+        // no ghosts at all are trivially all on Z nodes
+        let input = generator("L\n\nBBB = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)");
+        assert_eq!(ghost_walk(&input), Ok(0));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let network = generator(SAMPLE1B).network;
+        assert_eq!(
+            network.to_dot(|name| name == "AAA", |name| name == "ZZZ"),
+            r#"digraph network {
+    "AAA" [style=filled, fillcolor=palegreen];
+    "ZZZ" [style=filled, fillcolor=lightcoral];
+    "AAA" -> "BBB" [label=LR];
+    "BBB" -> "AAA" [label=L];
+    "BBB" -> "ZZZ" [label=R];
+    "ZZZ" -> "ZZZ" [label=LR];
+}
+"#
+        );
+
+        // ids starting with a digit are only valid unquoted when they are plain numbers
+        let network = generator(SAMPLE2).network;
+        let dot = network.to_dot(|name| name.ends_with('A'), |name| name.ends_with('Z'));
+        assert!(dot.contains(r#"    "11A" [style=filled, fillcolor=palegreen];"#));
+        assert!(dot.contains(r#"    "22Z" [style=filled, fillcolor=lightcoral];"#));
+        assert!(dot.contains(r#"    "11A" -> "11B" [label=L];"#));
+        assert!(dot.contains(r#"    "22C" -> "22Z" [label=LR];"#));
+    }
 }
@@ -1,4 +1,5 @@
-use itertools::Itertools;
+use crate::sequence::Polynomial;
+use num_bigint::BigInt;
 
 type Input = Vec<Vec<isize>>;
 
@@ -14,59 +15,20 @@ pub fn generator(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> isize {
-    let derivatives = input
-        .iter()
-        .map(|history| derivatives(history))
-        .collect_vec();
-    let integrals = derivatives.iter().map(integrals).collect_vec();
-
-    integrals
-        .iter()
-        .map(|history| history.last().unwrap().last().unwrap())
-        .sum()
+    extrapolate(input, |history| history.len() as isize)
 }
 
 pub fn part2(input: &Input) -> isize {
-    let mut input = input.clone();
-    for history in input.iter_mut() {
-        history.reverse();
-    }
-
-    let derivatives = input
-        .iter()
-        .map(|history| derivatives(history))
-        .collect_vec();
-    let integrals = derivatives.iter().map(integrals).collect_vec();
-
-    integrals
-        .iter()
-        .map(|history| history.last().unwrap().last().unwrap())
-        .sum()
-}
-
-fn derivatives(history: &[isize]) -> Input {
-    let mut current: Vec<isize> = history.to_vec();
-    let mut derivatives = Vec::from([current.clone()]);
-
-    while current.iter().sum::<isize>() != 0 {
-        current = current.windows(2).map(|each| each[1] - each[0]).collect();
-        derivatives.push(current.clone());
-    }
-
-    derivatives
+    extrapolate(input, |_| -1)
 }
 
-fn integrals(derivatives: &Input) -> Input {
-    let mut integrals = derivatives.clone();
-    integrals.reverse();
-
-    for i in 1..integrals.len() {
-        let current = *integrals[i].last().unwrap();
-        let previous = *integrals[i - 1].last().unwrap();
-        integrals[i].push(current + previous);
-    }
-
-    integrals
+fn extrapolate(input: &Input, offset: impl Fn(&[isize]) -> isize) -> isize {
+    input
+        .iter()
+        .map(|history| Polynomial::fit(history).at(offset(history)))
+        .sum::<BigInt>()
+        .try_into()
+        .expect("extrapolated sum overflowed")
 }
 
 #[cfg(test)]
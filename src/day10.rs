@@ -1,5 +1,6 @@
 use grid::*;
 use itertools::Itertools;
+use std::{fs, io, path::Path};
 
 pub struct Input {
     pipes: Pipes,
@@ -17,15 +18,15 @@ pub enum Pipe {
     #[default]
     Ground,
     Start,
-    Visited,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-struct Coord {
-    row: usize,
-    col: usize,
+pub struct Coord {
+    pub row: usize,
+    pub col: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Direction {
     Up,
     Down,
@@ -33,12 +34,6 @@ enum Direction {
     Right,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Location {
-    coord: Coord,
-    pipe: Pipe,
-}
-
 #[derive(Debug, Clone)]
 struct Pipes {
     grid: Grid<Pipe>,
@@ -52,7 +47,14 @@ impl Pipes {
             Direction::Up => {
                 if coord.row > 0 {
                     if let Some(pipe) = self.grid.get(coord.row - 1, coord.col) {
-                        if [Pipe::Vertical, Pipe::SouthEast, Pipe::SouthWest].contains(pipe) {
+                        if [
+                            Pipe::Vertical,
+                            Pipe::SouthEast,
+                            Pipe::SouthWest,
+                            Pipe::Start,
+                        ]
+                        .contains(pipe)
+                        {
                             neighbor = Some(Coord {
                                 row: coord.row - 1,
                                 col: coord.col,
@@ -64,7 +66,14 @@ impl Pipes {
             Direction::Down => {
                 if coord.row < self.grid.rows() - 1 {
                     if let Some(pipe) = self.grid.get(coord.row + 1, coord.col) {
-                        if [Pipe::Vertical, Pipe::NorthEast, Pipe::NorthWest].contains(pipe) {
+                        if [
+                            Pipe::Vertical,
+                            Pipe::NorthEast,
+                            Pipe::NorthWest,
+                            Pipe::Start,
+                        ]
+                        .contains(pipe)
+                        {
                             neighbor = Some(Coord {
                                 row: coord.row + 1,
                                 col: coord.col,
@@ -76,7 +85,14 @@ impl Pipes {
             Direction::Left => {
                 if coord.col > 0 {
                     if let Some(pipe) = self.grid.get(coord.row, coord.col - 1) {
-                        if [Pipe::Horizontal, Pipe::SouthEast, Pipe::NorthEast].contains(pipe) {
+                        if [
+                            Pipe::Horizontal,
+                            Pipe::SouthEast,
+                            Pipe::NorthEast,
+                            Pipe::Start,
+                        ]
+                        .contains(pipe)
+                        {
                             neighbor = Some(Coord {
                                 row: coord.row,
                                 col: coord.col - 1,
@@ -88,7 +104,14 @@ impl Pipes {
             Direction::Right => {
                 if coord.col < self.grid.cols() - 1 {
                     if let Some(pipe) = self.grid.get(coord.row, coord.col + 1) {
-                        if [Pipe::Horizontal, Pipe::SouthWest, Pipe::NorthWest].contains(pipe) {
+                        if [
+                            Pipe::Horizontal,
+                            Pipe::SouthWest,
+                            Pipe::NorthWest,
+                            Pipe::Start,
+                        ]
+                        .contains(pipe)
+                        {
                             neighbor = Some(Coord {
                                 row: coord.row,
                                 col: coord.col + 1,
@@ -102,27 +125,6 @@ impl Pipes {
         neighbor
     }
 
-    fn find_neighbors(&self, location: &Location) -> Vec<Location> {
-        let neighbors = self
-            .eligible_neighbors(&location.pipe)
-            .iter()
-            .filter_map(|direction| self.check_neighbor(&location.coord, direction))
-            .map(|coord| Location {
-                coord,
-                pipe: self.get_pipe(&coord),
-            })
-            .collect_vec();
-        if neighbors.is_empty() {
-            panic!("{:?} had no neighbors", location.coord);
-        };
-
-        neighbors
-    }
-
-    fn visisted(&mut self, coord: &Coord) {
-        self.grid[(coord.row, coord.col)] = Pipe::Visited
-    }
-
     fn get_pipe(&self, coord: &Coord) -> Pipe {
         self.grid[(coord.row, coord.col)]
     }
@@ -175,68 +177,155 @@ fn pipe_type(char: char) -> Pipe {
 }
 
 pub fn part1(input: &Input) -> usize {
-    let mut pipes: Pipes = input.pipes.clone();
-
-    visit_loop(&mut pipes, &input.start)
+    main_loop(input).len() / 2
 }
 
 pub fn part2(input: &Input) -> usize {
-    let mut input_grid = input.pipes.grid.to_owned();
-    let mut visited_pipes: Pipes = input.pipes.clone();
+    main_loop(input).enclosed()
+}
 
-    let start_pipe = get_start_pipe(&visited_pipes, &input.start);
+fn main_loop(input: &Input) -> PipeLoop {
+    PipeLoop::find(&input.pipes, input.start)
+        .unwrap_or_else(|| panic!("no loop runs through {:?}", input.start))
+}
 
-    visit_loop(&mut visited_pipes, &input.start);
+/// The main loop as tiles in walking order, beginning at the start
+#[derive(Debug, Clone)]
+pub struct PipeLoop {
+    path: Vec<Coord>,
+    start_pipe: Pipe,
+}
 
-    // swap start for a real pipe
-    input_grid[(input.start.row, input.start.col)] = start_pipe;
+impl PipeLoop {
+    /// try each way out of the start until one leads back to it
+    fn find(pipes: &Pipes, start: Coord) -> Option<PipeLoop> {
+        pipes
+            .eligible_neighbors(&Pipe::Start)
+            .into_iter()
+            .find_map(|direction| PipeLoop::follow(pipes, start, direction))
+    }
 
-    let mut count = 0;
+    fn follow(pipes: &Pipes, start: Coord, first: Direction) -> Option<PipeLoop> {
+        let mut path = vec![start];
+        let mut direction = first;
+        let mut coord = pipes.check_neighbor(&start, &direction)?;
+
+        while coord != start {
+            path.push(coord);
+            direction = pipes
+                .eligible_neighbors(&pipes.get_pipe(&coord))
+                .into_iter()
+                .find(|next| *next != direction.opposite())
+                .unwrap();
+            coord = pipes.check_neighbor(&coord, &direction)?;
+        }
 
-    for row in 0..input_grid.rows() {
-        let mut inside = false;
+        let start_pipe = match (first, direction.opposite()) {
+            (Direction::Up, Direction::Down) | (Direction::Down, Direction::Up) => Pipe::Vertical,
+            (Direction::Left, Direction::Right) | (Direction::Right, Direction::Left) => {
+                Pipe::Horizontal
+            }
+            (Direction::Up, Direction::Right) | (Direction::Right, Direction::Up) => {
+                Pipe::NorthEast
+            }
+            (Direction::Up, Direction::Left) | (Direction::Left, Direction::Up) => Pipe::NorthWest,
+            (Direction::Down, Direction::Left) | (Direction::Left, Direction::Down) => {
+                Pipe::SouthWest
+            }
+            (Direction::Down, Direction::Right) | (Direction::Right, Direction::Down) => {
+                Pipe::SouthEast
+            }
+            _ => return None,
+        };
 
-        count += input_grid
-            .iter_row(row)
-            .enumerate()
-            .filter(|(col, pipe)| {
-                if should_flip(pipe) && visited_pipes.grid.get(row, *col).unwrap() == &Pipe::Visited
-                {
-                    inside ^= true
-                }
-                inside && visited_pipes.grid.get(row, *col).unwrap() != &Pipe::Visited
-            })
-            .count();
+        Some(PipeLoop { path, start_pipe })
     }
 
-    count
-}
+    #[allow(dead_code)]
+    pub fn path(&self) -> &[Coord] {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// the pipe hidden under the start tile
+    #[allow(dead_code)]
+    pub fn start_pipe(&self) -> Pipe {
+        self.start_pipe
+    }
 
-fn visit_loop(pipes: &mut Pipes, start: &Coord) -> usize {
-    let mut count: usize = 1;
-    let mut paths = pipes.find_neighbors(&Location {
-        coord: *start,
-        pipe: Pipe::Start,
-    });
+    /// tiles where the loop turns
+    #[allow(dead_code)]
+    pub fn corners(&self) -> Vec<Coord> {
+        self.path
+            .iter()
+            .circular_tuple_windows()
+            .filter(|(previous, _, next)| previous.row != next.row && previous.col != next.col)
+            .map(|(_, corner, _)| *corner)
+            .collect()
+    }
 
-    pipes.visisted(start);
-    paths
-        .iter()
-        .for_each(|location| pipes.visisted(&location.coord));
+    /// top left and bottom right tiles, inclusive
+    #[allow(dead_code)]
+    pub fn bounding_box(&self) -> (Coord, Coord) {
+        let (top, bottom) = self
+            .path
+            .iter()
+            .map(|coord| coord.row)
+            .minmax()
+            .into_option()
+            .unwrap();
+        let (left, right) = self
+            .path
+            .iter()
+            .map(|coord| coord.col)
+            .minmax()
+            .into_option()
+            .unwrap();
+        (
+            Coord {
+                row: top,
+                col: left,
+            },
+            Coord {
+                row: bottom,
+                col: right,
+            },
+        )
+    }
 
-    while paths[0].coord != paths[1].coord {
-        let next = paths
+    /// twice the area within the loop's centre line, from the shoelace formula
+    fn double_area(&self) -> usize {
+        self.path
             .iter()
-            .map(|location| pipes.find_neighbors(location)[0])
-            .collect_vec();
-        paths.iter_mut().enumerate().for_each(|(i, location)| {
-            pipes.visisted(&next[i].coord);
-            *location = next[i];
-        });
-        count += 1;
+            .circular_tuple_windows()
+            .map(|(a, b)| a.row as isize * b.col as isize - b.row as isize * a.col as isize)
+            .sum::<isize>()
+            .unsigned_abs()
     }
 
-    count
+    /// tiles inside the loop, from Pick's theorem A = i + b / 2 - 1
+    pub fn enclosed(&self) -> usize {
+        (self.double_area() + 2 - self.len()) / 2
+    }
+}
+
+impl Direction {
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
 }
 
 fn find_start(input: &Grid<Pipe>) -> Coord {
@@ -253,53 +342,152 @@ fn find_start(input: &Grid<Pipe>) -> Coord {
     start.unwrap_or_else(|| panic!("no start was found"))
 }
 
-fn get_start_pipe(pipes: &Pipes, start: &Coord) -> Pipe {
-    let binding = pipes.eligible_neighbors(&Pipe::Start);
-    let directions = binding
-        .iter()
-        .filter(|direction| pipes.check_neighbor(start, direction).is_some())
-        .collect_vec();
-
-    match directions[..] {
-        [Direction::Up, Direction::Down] => Pipe::Vertical,
-        [Direction::Left, Direction::Right] => Pipe::Horizontal,
-        [Direction::Up, Direction::Right] => Pipe::NorthEast,
-        [Direction::Up, Direction::Left] => Pipe::NorthWest,
-        [Direction::Down, Direction::Left] => Pipe::SouthWest,
-        [Direction::Down, Direction::Right] => Pipe::SouthEast,
-        _ => panic!("unknown direction combination"),
-    }
+/// How a tile sits relative to the main loop
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+enum Region {
+    #[default]
+    Outside,
+    Inside,
+    Loop,
 }
 
-fn should_flip(pipe: &Pipe) -> bool {
-    [Pipe::Vertical, Pipe::NorthWest, Pipe::NorthEast].contains(pipe)
+/// the input grid with the start swapped for the pipe it hides
+fn resolved_grid(input: &Input, pipe_loop: &PipeLoop) -> Grid<Pipe> {
+    let mut grid = input.pipes.grid.clone();
+    grid[(input.start.row, input.start.col)] = pipe_loop.start_pipe();
+    grid
 }
 
-#[allow(dead_code)]
-fn reverse_pipe(pipe: &Pipe) -> char {
+fn regions(grid: &Grid<Pipe>, pipe_loop: &PipeLoop) -> Grid<Region> {
+    let mut regions: Grid<Region> = Grid::new(grid.rows(), grid.cols());
+    for coord in pipe_loop.path() {
+        regions[(coord.row, coord.col)] = Region::Loop;
+    }
+
+    // crossing a loop pipe that reaches north flips inside and outside
+    for row in 0..grid.rows() {
+        let mut inside = false;
+        for col in 0..grid.cols() {
+            if regions[(row, col)] == Region::Loop {
+                if [Pipe::Vertical, Pipe::NorthEast, Pipe::NorthWest].contains(&grid[(row, col)]) {
+                    inside ^= true;
+                }
+            } else if inside {
+                regions[(row, col)] = Region::Inside;
+            }
+        }
+    }
+
+    regions
+}
+
+fn box_drawing(pipe: &Pipe) -> char {
     match pipe {
-        Pipe::Vertical => '|',
-        Pipe::Horizontal => '-',
-        Pipe::NorthEast => 'L',
-        Pipe::NorthWest => 'J',
-        Pipe::SouthWest => '7',
-        Pipe::SouthEast => 'F',
-        Pipe::Ground => '.',
+        Pipe::Vertical => '│',
+        Pipe::Horizontal => '─',
+        Pipe::NorthEast => '└',
+        Pipe::NorthWest => '┘',
+        Pipe::SouthWest => '┐',
+        Pipe::SouthEast => '┌',
+        Pipe::Ground => ' ',
         Pipe::Start => 'S',
-        Pipe::Visited => 'x',
     }
 }
 
+/// box-drawn map for a terminal, with the loop in bold yellow,
+/// enclosed tiles on green and junk pipes dimmed
 #[allow(dead_code)]
-fn print_grid(grid: &Grid<Pipe>) {
+pub fn render_ansi(input: &Input) -> String {
+    let pipe_loop = main_loop(input);
+    let grid = resolved_grid(input, &pipe_loop);
+    let regions = regions(&grid, &pipe_loop);
+
+    let mut output = String::new();
     for row in 0..grid.rows() {
-        println!("{:?}", grid.iter_row(row).map(reverse_pipe).join(""));
+        for col in 0..grid.cols() {
+            let tile = box_drawing(&grid[(row, col)]);
+            let style = match regions[(row, col)] {
+                Region::Loop => "1;33",
+                Region::Inside => "2;42",
+                Region::Outside => "2",
+            };
+            output += &format!("\x1b[{style}m{tile}\x1b[0m");
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// the same picture as `render_ansi` as an SVG document
+#[allow(dead_code)]
+pub fn render_svg(input: &Input) -> String {
+    const CELL: usize = 12;
+    let pipe_loop = main_loop(input);
+    let grid = resolved_grid(input, &pipe_loop);
+    let regions = regions(&grid, &pipe_loop);
+    let (width, height) = (grid.cols() * CELL, grid.rows() * CELL);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg += "<style>path { fill: none; stroke-linecap: round; } \
+        .loop { stroke: #d04000; stroke-width: 4; } \
+        .junk { stroke: #c0c0c0; stroke-width: 2; }</style>\n";
+    svg += &format!("<rect width=\"{width}\" height=\"{height}\" fill=\"white\"/>\n");
+
+    for ((row, col), region) in regions.indexed_iter() {
+        if region == &Region::Inside {
+            let (x, y) = (col * CELL, row * CELL);
+            svg += &format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"#b8e8b8\"/>\n"
+            );
+        }
     }
+
+    for ((row, col), pipe) in grid.indexed_iter() {
+        if pipe == &Pipe::Ground {
+            continue;
+        }
+        let (x, y) = (col * CELL + CELL / 2, row * CELL + CELL / 2);
+        let class = if regions[(row, col)] == Region::Loop {
+            "loop"
+        } else {
+            "junk"
+        };
+        // a line from each connected edge into the middle of the tile
+        let [from, to] = input
+            .pipes
+            .eligible_neighbors(pipe)
+            .iter()
+            .map(|direction| match direction {
+                Direction::Up => (x, y - CELL / 2),
+                Direction::Down => (x, y + CELL / 2),
+                Direction::Left => (x - CELL / 2, y),
+                Direction::Right => (x + CELL / 2, y),
+            })
+            .collect_vec()[..]
+        else {
+            unreachable!("pipes have two ends")
+        };
+        svg += &format!(
+            "<path class=\"{class}\" d=\"M {} {} L {x} {y} L {} {}\"/>\n",
+            from.0, from.1, to.0, to.1
+        );
+    }
+
+    svg + "</svg>\n"
+}
+
+#[allow(dead_code)]
+pub fn save_svg(input: &Input, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, render_svg(input))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use regex::Regex;
 
     const SAMPLE1A: &str = ".....
 .S-7.
@@ -351,10 +539,50 @@ L7JLJL-JLJLJL--JLJ.L";
         assert_eq!(part1(&generator(SAMPLE1B)), 8);
     }
 
+    const SAMPLE_BRANCHES: &str = ".|...
+-S-7.
+.|.|.
+.L-J.
+.....";
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE2A)), 4);
         assert_eq!(part2(&generator(SAMPLE2B)), 8);
         assert_eq!(part2(&generator(SAMPLE2C)), 10);
     }
+
+    #[test]
+    fn test_pipe_loop() {
+        let pipe_loop = main_loop(&generator(SAMPLE_BRANCHES));
+        assert_eq!(pipe_loop.start_pipe(), Pipe::SouthEast);
+        assert_eq!(pipe_loop.len(), 8);
+        assert_eq!(pipe_loop.enclosed(), 1);
+        assert_eq!(pipe_loop.path()[1], Coord { row: 2, col: 1 });
+        assert_eq!(pipe_loop.corners().len(), 4);
+        assert_eq!(
+            pipe_loop.bounding_box(),
+            (Coord { row: 1, col: 1 }, Coord { row: 3, col: 3 })
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let input = generator(SAMPLE_BRANCHES);
+        let plain = Regex::new("\x1b\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(&render_ansi(&input), "")
+            .into_owned();
+        assert_eq!(plain, " │   \n─┌─┐ \n │ │ \n └─┘ \n     \n");
+        assert!(render_ansi(&input).contains("\x1b[2;42m \x1b[0m"));
+
+        let svg = render_svg(&input);
+        assert_eq!(svg.matches("class=\"loop\"").count(), 8);
+        assert_eq!(svg.matches("class=\"junk\"").count(), 2);
+        assert_eq!(svg.matches("#b8e8b8").count(), 1);
+        assert_eq!(
+            render_svg(&generator(SAMPLE2C)).matches("#b8e8b8").count(),
+            10
+        );
+    }
 }
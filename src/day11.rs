@@ -3,7 +3,6 @@ use itertools::Itertools;
 
 pub struct Input {
     galaxies: Vec<Galaxy>,
-    grid: Grid<char>,
 }
 
 #[derive(Debug, Clone)]
@@ -22,90 +21,91 @@ pub fn generator(input: &str) -> Input {
     }
 
     let galaxies = grid
-        .iter()
-        .enumerate()
-        .filter_map(|(i, each)| {
-            if each == &'#' {
-                Some(Galaxy {
-                    row: i / grid.rows(),
-                    col: i % grid.rows(),
-                })
-            } else {
-                None
-            }
-        })
+        .indexed_iter()
+        .filter(|(_, each)| each == &&'#')
+        .map(|((row, col), _)| Galaxy { row, col })
         .collect_vec();
 
-    Input { galaxies, grid }
+    Input { galaxies }
 }
 
-pub fn part1(input: &Input) -> isize {
-    main(input, 1)
+pub fn part1(input: &Input) -> u64 {
+    Expansion::new(input).distance_sum(2)
 }
 
-pub fn part2(input: &Input) -> isize {
-    main(input, 999_999)
+pub fn part2(input: &Input) -> u64 {
+    Expansion::new(input).distance_sum(1_000_000)
 }
 
-fn main(input: &Input, by: usize) -> isize {
-    let mut galaxies = input.galaxies.clone();
+/// Sum of pairwise galaxy distances as a line in the expansion factor,
+/// split into fixed distance and the empty rows and columns crossed
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    fixed: u64,
+    empty: u64,
+}
 
-    expand_the_universe(&mut galaxies, by, &input.grid);
+impl Expansion {
+    pub fn new(input: &Input) -> Self {
+        let rows = input.galaxies.iter().map(|galaxy| galaxy.row).collect_vec();
+        let cols = input.galaxies.iter().map(|galaxy| galaxy.col).collect_vec();
+        let (row_fixed, row_empty) = axis_distances(rows);
+        let (col_fixed, col_empty) = axis_distances(cols);
+
+        Expansion {
+            fixed: row_fixed + col_fixed,
+            empty: row_empty + col_empty,
+        }
+    }
 
-    let combinations = (0..galaxies.len()).tuple_combinations();
+    /// every empty row and column becomes `factor` rows or columns wide
+    pub fn distance_sum(&self, factor: u64) -> u64 {
+        self.fixed + factor * self.empty
+    }
 
-    combinations
-        .map(|(a, b)| manhattan_distance(&galaxies[a], &galaxies[b]))
-        .sum()
+    #[allow(dead_code)]
+    pub fn distance_sums(&self, factors: &[u64]) -> Vec<u64> {
+        factors
+            .iter()
+            .map(|factor| self.distance_sum(*factor))
+            .collect()
+    }
 }
 
-fn expand_the_universe(galaxies: &mut [Coord], by: usize, grid: &Grid<char>) {
-    let mut empty_rows = grid
-        .iter_rows()
-        .enumerate()
-        .filter_map(|(i, mut row)| {
-            if row.all(|x| x == &'.') {
-                Some(i)
-            } else {
-                None
-            }
-        })
-        .collect_vec();
-    let mut empty_cols = grid
-        .iter_cols()
-        .enumerate()
-        .filter_map(|(i, mut col)| {
-            if col.all(|x| x == &'.') {
-                Some(i)
-            } else {
-                None
+/// pairwise distances along one axis, as (occupied lines, empty lines) crossed
+fn axis_distances(mut positions: Vec<usize>) -> (u64, u64) {
+    positions.sort_unstable();
+
+    // empty lines before a position are the ones not taken by a smaller galaxy
+    let mut occupied = 0;
+    let mut previous = None;
+    let empties = positions
+        .iter()
+        .map(|position| {
+            if previous != Some(*position) {
+                occupied += 1;
+                previous = Some(*position);
             }
+            (*position + 1 - occupied) as u64
         })
         .collect_vec();
 
-    empty_rows.reverse();
-    empty_cols.reverse();
-
-    empty_rows.iter().for_each(|row| {
-        galaxies.iter_mut().for_each(|galaxy| {
-            if galaxy.row > *row {
-                galaxy.row += by
-            }
-        })
-    });
-    empty_cols.iter().for_each(|col| {
-        galaxies.iter_mut().for_each(|galaxy| {
-            if galaxy.col > *col {
-                galaxy.col += by
-            }
-        })
-    });
+    let total = pairwise_sum(positions.iter().map(|position| *position as u64));
+    let empty = pairwise_sum(empties.into_iter());
+    (total - empty, empty)
 }
 
-fn manhattan_distance(a: &Coord, b: &Coord) -> isize {
-    (a.col.abs_diff(b.col) + a.row.abs_diff(b.row))
-        .try_into()
-        .unwrap()
+/// sum of differences between every pair of sorted values, via prefix sums
+fn pairwise_sum(sorted: impl Iterator<Item = u64>) -> u64 {
+    let mut prefix = 0;
+    sorted
+        .enumerate()
+        .map(|(i, value)| {
+            let sum = value * i as u64 - prefix;
+            prefix += value;
+            sum
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -130,7 +130,15 @@ mod tests {
 
     #[test]
     fn test_part2() {
-        assert_eq!(main(&generator(SAMPLE), 9), 1030);
-        assert_eq!(main(&generator(SAMPLE), 99), 8410);
+        let expansion = Expansion::new(&generator(SAMPLE));
+        assert_eq!(expansion.distance_sum(10), 1030);
+        assert_eq!(expansion.distance_sums(&[1, 2, 100]), vec![292, 374, 8410]);
+    }
+
+    #[test]
+    fn test_non_square() {
+        let input = generator("#..\n...\n...\n..#\n#..");
+        // rows 1 and 2 are empty, as is column 1
+        assert_eq!(Expansion::new(&input).distance_sums(&[1, 3]), vec![12, 24]);
     }
 }
@@ -1,5 +1,6 @@
 use itertools::Itertools;
-use memoize::memoize;
+use rand::Rng;
+use rayon::prelude::*;
 
 type Input = Vec<Record>;
 
@@ -9,6 +10,91 @@ pub struct Record {
     groups: Vec<usize>,
 }
 
+impl Record {
+    #[allow(dead_code)]
+    pub fn count(&self) -> u128 {
+        chomp(self.springs.as_bytes(), &self.groups)
+    }
+
+    /// every valid arrangement in lexicographic order, with '#' before '.'
+    #[allow(dead_code)]
+    pub fn arrangements(&self) -> Arrangements<'_> {
+        Arrangements {
+            record: self,
+            table: arrangement_table(self.springs.as_bytes(), &self.groups),
+            next: 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn nth_arrangement(&self, k: u128) -> Option<String> {
+        let table = arrangement_table(self.springs.as_bytes(), &self.groups);
+        nth_arrangement(self, &table, k)
+    }
+
+    /// uniformly random arrangement, picked by index using the DP counts
+    #[allow(dead_code)]
+    pub fn random_arrangement(&self, rng: &mut impl Rng) -> Option<String> {
+        let table = arrangement_table(self.springs.as_bytes(), &self.groups);
+        match table[0][0] {
+            0 => None,
+            count => nth_arrangement(self, &table, rng.gen_range(0..count)),
+        }
+    }
+}
+
+/// Lazily walks the arrangements of a record by index
+pub struct Arrangements<'a> {
+    record: &'a Record,
+    table: Vec<Vec<u128>>,
+    next: u128,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let arrangement = nth_arrangement(self.record, &self.table, self.next)?;
+        self.next += 1;
+        Some(arrangement)
+    }
+}
+
+/// follow the DP table down, skipping whole branches that come before k
+fn nth_arrangement(record: &Record, table: &[Vec<u128>], mut k: u128) -> Option<String> {
+    let springs = record.springs.as_bytes();
+    let groups = &record.groups;
+    let (n, m) = (springs.len(), groups.len());
+    if k >= table[0][0] {
+        return None;
+    }
+
+    let runs = damaged_runs(springs);
+    let mut arrangement = String::with_capacity(n);
+    let (mut i, mut j) = (0, 0);
+
+    while i < n {
+        // a group starting here sorts before an operational spring
+        if springs[i] != b'.' && j < m && fits(springs, &runs, i, groups[j]) {
+            let next = (i + groups[j] + 1).min(n);
+            let count = table[next][j + 1];
+            if k < count {
+                arrangement += &"#".repeat(groups[j]);
+                if next > i + groups[j] {
+                    arrangement.push('.');
+                }
+                (i, j) = (next, j + 1);
+                continue;
+            }
+            k -= count;
+        }
+        arrangement.push('.');
+        i += 1;
+    }
+
+    Some(arrangement)
+}
+
 pub fn generator(input: &str) -> Input {
     input
         .lines()
@@ -27,83 +113,77 @@ pub fn generator(input: &str) -> Input {
         .collect_vec()
 }
 
-pub fn part1(input: &Input) -> usize {
+pub fn part1(input: &Input) -> u128 {
     input
-        .iter()
-        .map(|each| chomp(each.springs.clone(), each.groups.clone()))
+        .par_iter()
+        .map(|each| chomp(each.springs.as_bytes(), &each.groups))
         .sum()
 }
 
-pub fn part2(input: &Input) -> usize {
+pub fn part2(input: &Input) -> u128 {
     input
-        .iter()
-        .map(unfold)
-        .map(|each| chomp(each.springs.clone(), each.groups.clone()))
+        .par_iter()
+        .map(|each| unfold(each, 5))
+        .map(|each| chomp(each.springs.as_bytes(), &each.groups))
         .sum()
 }
 
-#[memoize]
-fn chomp(springs: String, groups: Vec<usize>) -> usize {
-    let mut arrangements = 0;
+pub fn chomp(springs: &[u8], groups: &[usize]) -> u128 {
+    arrangement_table(springs, groups)[0][0]
+}
 
-    if springs.is_empty() {
-        return if groups.is_empty() { 1 } else { 0 };
-    } else if groups.is_empty() && springs.chars().any(|c| c == '#') {
-        return 0;
-    }
+/// table[i][j] is the number of ways to arrange springs[i..] into groups[j..]
+pub fn arrangement_table(springs: &[u8], groups: &[usize]) -> Vec<Vec<u128>> {
+    let (n, m) = (springs.len(), groups.len());
+    let runs = damaged_runs(springs);
 
-    let first = &springs[..1];
-    if first == "." {
-        arrangements += chomp(springs[1..].to_string(), groups.clone());
-    }
-    if first == "?" {
-        arrangements += chomp(format!(".{}", &springs[1..]), groups.clone());
-        arrangements += chomp(format!("#{}", &springs[1..]), groups.clone());
-    }
-    if first == "#" {
-        // at least enough springs to satisfy the group
-        if springs.len() < groups[0] {
-            return 0;
-        }
-        // found a complete group
-        if springs[..groups[0]]
-            .chars()
-            .all(|c| ['#', '?'].contains(&c))
-        {
-            // check spring after the group
-            if springs.len() > groups[0] {
-                let next = &springs[groups[0]..groups[0] + 1];
-                // too big for the group
-                if next == "#" {
-                    return 0;
-                }
-                // the next character needs to be a . for the group to be valid so consume it
-                arrangements += chomp(springs[groups[0] + 1..].to_string(), groups[1..].to_vec());
-            } else {
-                // end of the springs
-                arrangements += chomp(springs[groups[0]..].to_string(), groups[1..].to_vec());
+    let mut table = vec![vec![0; m + 1]; n + 1];
+    table[n][m] = 1;
+
+    for i in (0..n).rev() {
+        for j in (0..=m).rev() {
+            let mut arrangements = 0;
+            if springs[i] != b'#' {
+                arrangements += table[i + 1][j];
+            }
+            if springs[i] != b'.' && j < m && fits(springs, &runs, i, groups[j]) {
+                // consume the group and the operational spring after it
+                arrangements += table[(i + groups[j] + 1).min(n)][j + 1];
             }
+            table[i][j] = arrangements;
         }
     }
-    // if arrangements > 0 {
-    //     println!(
-    //         "springs: {:?}, groups: {:?}, arrangments: {}",
-    //         springs, groups, arrangements
-    //     );
-    // }
-    arrangements
+
+    table
 }
 
-fn unfold(record: &Record) -> Record {
+/// springs that could be damaged in a row from each position
+fn damaged_runs(springs: &[u8]) -> Vec<usize> {
+    let mut runs = vec![0; springs.len() + 1];
+    for i in (0..springs.len()).rev() {
+        if springs[i] != b'.' {
+            runs[i] = runs[i + 1] + 1;
+        }
+    }
+    runs
+}
+
+/// a group of this size can start at i without running into another damaged spring
+fn fits(springs: &[u8], runs: &[usize], i: usize, size: usize) -> bool {
+    runs[i] >= size && springs.get(i + size) != Some(&b'#')
+}
+
+pub fn unfold(record: &Record, times: usize) -> Record {
     Record {
-        springs: format!("{}?", record.springs).repeat(4) + &record.springs.to_string(),
-        groups: record.groups.repeat(5),
+        springs: vec![record.springs.as_str(); times].join("?"),
+        groups: record.groups.repeat(times),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
 
     const SAMPLE: &str = "???.### 1,1,3
 .??..??...?##. 1,1,3
@@ -121,4 +201,34 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 525152);
     }
+
+    #[test]
+    fn test_unfold() {
+        let record = &generator("?###???????? 3,2,1")[0];
+        assert_eq!(chomp(b"", &[]), 1);
+        assert_eq!(chomp(b"#", &[]), 0);
+        assert_eq!(chomp(b"??", &[1]), 2);
+
+        let unfolded = unfold(record, 20);
+        assert_eq!(unfolded.springs.len(), 12 * 20 + 19);
+        assert!(chomp(unfolded.springs.as_bytes(), &unfolded.groups) > u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let record = &generator("?###???????? 3,2,1")[0];
+        let arrangements = record.arrangements().collect_vec();
+        assert_eq!(arrangements.len() as u128, record.count());
+        assert_eq!(arrangements[0], ".###.##.#...");
+        assert_eq!(arrangements[9], ".###....##.#");
+        assert!(arrangements.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(record.nth_arrangement(3), Some(arrangements[3].clone()));
+        assert_eq!(record.nth_arrangement(10), None);
+
+        let mut rng = StdRng::seed_from_u64(12);
+        for _ in 0..20 {
+            let arrangement = record.random_arrangement(&mut rng).unwrap();
+            assert!(arrangements.contains(&arrangement));
+        }
+    }
 }
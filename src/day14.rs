@@ -1,5 +1,10 @@
+use crate::cycle;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
 
 type Input = Vec<Vec<char>>;
 
@@ -8,120 +13,344 @@ pub fn generator(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> usize {
-    let mut grid = input.clone();
-    tilt_north(&mut grid);
+    let (platform, mut rocks) = Platform::new(input);
+    platform.tilt(&mut rocks, Direction::North);
 
-    support_beams(&grid)
+    platform.north_load(&rocks)
 }
 
 pub fn part2(input: &Input) -> usize {
-    const MAX: usize = 1_000_000_000;
-    let mut grid = input.clone();
+    let (platform, rocks) = Platform::new(input);
+    let (cycle, states) = cycle::detect(rocks, |rocks| {
+        let mut rocks = rocks.clone();
+        platform.spin_cycle(&mut rocks);
+        rocks
+    });
 
-    let mut unique_cycles: HashMap<String, usize> = HashMap::new();
-    let mut cycle_size: usize = 0;
-    let mut cycle: usize = 0;
+    platform.north_load(&states[cycle.index(1_000_000_000)])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
 
-    for i in 0..1_000_000_000 {
-        spin_cycle(&mut grid);
+/// Round rocks as one bit per cell, in row-major order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rocks(Vec<u64>);
 
-        let key = stringify(&grid);
-        if let Some(value) = unique_cycles.get(&key) {
-            cycle = i;
-            cycle_size = i - value;
-            break;
+impl Rocks {
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        if value {
+            self.0[i / 64] |= 1 << (i % 64);
         } else {
-            unique_cycles.insert(key.clone(), i);
+            self.0[i / 64] &= !(1 << (i % 64));
         }
     }
-    // println!("cycle start: {}", cycle_start);
-    // println!("cycle size: {}", cycle_size);
-    // println!("cycles remaining: {}", (MAX - cycle) % cycle_size);
 
-    for _ in 0..(MAX - cycle - 1) % cycle_size {
-        spin_cycle(&mut grid);
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(word, bits)| {
+            (0..64)
+                .filter(move |bit| bits >> bit & 1 == 1)
+                .map(move |bit| word * 64 + bit)
+        })
     }
+}
 
-    support_beams(&grid)
+/// Cells between cube rocks along a line, listed from the end rocks roll towards
+#[derive(Debug)]
+struct Segment {
+    start: usize,
+    stride: isize,
+    len: usize,
 }
 
-fn spin_cycle(grid: &mut Input) {
-    tilt_north(grid);
-    tilt_west(grid);
-    tilt_south(grid);
-    tilt_east(grid);
+impl Segment {
+    fn cell(&self, k: usize) -> usize {
+        (self.start as isize + self.stride * k as isize) as usize
+    }
+}
+
+/// The fixed parts of the platform, with the segments rocks settle into for each direction
+#[derive(Debug)]
+pub struct Platform {
+    width: usize,
+    height: usize,
+    cubes: Vec<bool>,
+    segments: [Vec<Segment>; 4],
 }
 
-fn tilt_north(grid: &mut Input) {
-    fn move_up(grid: &mut Input, row: usize, col: usize) {
-        if grid[row][col] == 'O' && row > 0 && grid[row - 1][col] == '.' {
-            grid[row - 1][col] = 'O';
-            grid[row][col] = '.';
-            move_up(grid, row - 1, col);
+impl Platform {
+    pub fn new(input: &Input) -> (Platform, Rocks) {
+        let (height, width) = (input.len(), input.first().map_or(0, |row| row.len()));
+        let cells = input.iter().flatten().collect_vec();
+        let cubes = cells.iter().map(|cell| **cell == '#').collect_vec();
+
+        let mut rocks = Rocks(vec![0; cells.len().div_ceil(64)]);
+        for (i, cell) in cells.iter().enumerate() {
+            rocks.set(i, **cell == 'O');
         }
+
+        let mut platform = Platform {
+            width,
+            height,
+            cubes,
+            segments: Default::default(),
+        };
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            platform.segments[direction as usize] = platform.find_segments(direction);
+        }
+
+        (platform, rocks)
     }
 
-    for col in 0..grid[0].len() {
-        for row in 0..grid.len() {
-            move_up(grid, row, col);
+    /// each line as (first cell, stride, length), starting on the side rocks roll towards
+    fn lines(&self, direction: Direction) -> Vec<(usize, isize, usize)> {
+        let (width, height) = (self.width, self.height);
+        match direction {
+            Direction::North => (0..width)
+                .map(|col| (col, width as isize, height))
+                .collect(),
+            Direction::South => (0..width)
+                .map(|col| ((height - 1) * width + col, -(width as isize), height))
+                .collect(),
+            Direction::West => (0..height).map(|row| (row * width, 1, width)).collect(),
+            Direction::East => (0..height)
+                .map(|row| (row * width + width - 1, -1, width))
+                .collect(),
         }
     }
-}
 
-fn tilt_west(grid: &mut Input) {
-    fn move_left(grid: &mut Input, row: usize, col: usize) {
-        if grid[row][col] == 'O' && col > 0 && grid[row][col - 1] == '.' {
-            grid[row][col - 1] = 'O';
-            grid[row][col] = '.';
-            move_left(grid, row, col - 1);
+    fn find_segments(&self, direction: Direction) -> Vec<Segment> {
+        let mut segments = Vec::new();
+
+        for (first, stride, len) in self.lines(direction) {
+            let cell = |k: usize| (first as isize + stride * k as isize) as usize;
+            let mut k = 0;
+            while k < len {
+                if self.cubes[cell(k)] {
+                    k += 1;
+                    continue;
+                }
+                let start = k;
+                while k < len && !self.cubes[cell(k)] {
+                    k += 1;
+                }
+                segments.push(Segment {
+                    start: cell(start),
+                    stride,
+                    len: k - start,
+                });
+            }
         }
+
+        segments
     }
 
-    for row in 0..grid.len() {
-        for col in 0..grid[0].len() {
-            move_left(grid, row, col);
+    /// roll every round rock as far as it goes, one pass per segment
+    pub fn tilt(&self, rocks: &mut Rocks, direction: Direction) {
+        for segment in &self.segments[direction as usize] {
+            let mut count = 0;
+            for k in 0..segment.len {
+                let cell = segment.cell(k);
+                if rocks.get(cell) {
+                    count += 1;
+                    rocks.set(cell, false);
+                }
+            }
+            for k in 0..count {
+                rocks.set(segment.cell(k), true);
+            }
         }
     }
-}
 
-fn tilt_south(grid: &mut Input) {
-    fn move_down(grid: &mut Input, row: usize, col: usize) {
-        if grid[row][col] == 'O' && row < grid.len() - 1 && grid[row + 1][col] == '.' {
-            grid[row + 1][col] = 'O';
-            grid[row][col] = '.';
-            move_down(grid, row + 1, col);
+    pub fn spin_cycle(&self, rocks: &mut Rocks) {
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            self.tilt(rocks, direction);
         }
     }
 
-    for col in 0..grid[0].len() {
-        for row in (0..grid.len()).rev() {
-            move_down(grid, row, col);
+    pub fn north_load(&self, rocks: &Rocks) -> usize {
+        rocks.iter().map(|i| self.height - i / self.width).sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn to_grid(&self, rocks: &Rocks) -> Input {
+        (0..self.height)
+            .map(|row| {
+                (0..self.width)
+                    .map(|col| {
+                        let i = row * self.width + col;
+                        if self.cubes[i] {
+                            '#'
+                        } else if rocks.get(i) {
+                            'O'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// One state in a recording of the spin cycle
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Frame {
+    /// which spin cycle the tilt belongs to, counting from 0
+    pub spin: usize,
+    /// the tilt that produced this frame, None for the starting layout
+    pub tilt: Option<Direction>,
+    pub rocks: Rocks,
+    pub load: usize,
+    /// the first state the spin cycle returns to
+    pub cycle_start: bool,
+}
+
+/// every tilt from the start until the first full lap of the detected cycle
+#[allow(dead_code)]
+pub fn record_frames(input: &Input) -> (Platform, Vec<Frame>) {
+    let (platform, mut rocks) = Platform::new(input);
+    let (cycle, _) = cycle::detect(rocks.clone(), |rocks| {
+        let mut rocks = rocks.clone();
+        platform.spin_cycle(&mut rocks);
+        rocks
+    });
+
+    let mut frames = vec![Frame {
+        spin: 0,
+        tilt: None,
+        load: platform.north_load(&rocks),
+        rocks: rocks.clone(),
+        cycle_start: cycle.mu == 0,
+    }];
+    for spin in 0..cycle.mu + cycle.lambda {
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            platform.tilt(&mut rocks, direction);
+            frames.push(Frame {
+                spin,
+                tilt: Some(direction),
+                load: platform.north_load(&rocks),
+                rocks: rocks.clone(),
+                cycle_start: direction == Direction::East && spin + 1 == cycle.mu,
+            });
         }
     }
+
+    (platform, frames)
 }
 
-fn tilt_east(grid: &mut Input) {
-    fn move_right(grid: &mut Input, row: usize, col: usize) {
-        if grid[row][col] == 'O' && col < grid[row].len() - 1 && grid[row][col + 1] == '.' {
-            grid[row][col + 1] = 'O';
-            grid[row][col] = '.';
-            move_right(grid, row, col + 1);
+const CELL: usize = 6;
+const BANNER: usize = 9;
+// floor, cube rock, round rock, banner, text, cycle marker
+const PALETTE: [u8; 18] = [
+    0xe8, 0xe0, 0xd0, 0x40, 0x48, 0x58, 0xe0, 0x70, 0x20, 0x10, 0x10, 0x10, 0xff, 0xff, 0xff, 0xd0,
+    0x20, 0x20,
+];
+// 3x5 digits, top row in the highest bits
+const DIGITS: [u16; 10] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b111_001_111_100_111,
+    0b111_001_111_001_111,
+    0b101_101_111_001_001,
+    0b111_100_111_001_111,
+    0b111_100_111_101_111,
+    0b111_001_001_001_001,
+    0b111_101_111_101_111,
+    0b111_101_111_001_111,
+];
+
+/// palette indexed pixels for one frame, with the north load across the top
+fn draw_frame(platform: &Platform, frame: &Frame, in_cycle: bool) -> Vec<u8> {
+    let width = platform.width * CELL;
+    let mut pixels = vec![3; width * (platform.height * CELL + BANNER)];
+
+    for (row, line) in platform.to_grid(&frame.rocks).iter().enumerate() {
+        for (col, cell) in line.iter().enumerate() {
+            let colour = match cell {
+                '#' => 1,
+                'O' => 2,
+                _ => 0,
+            };
+            for y in 0..CELL {
+                let start = (BANNER + row * CELL + y) * width + col * CELL;
+                pixels[start..start + CELL].fill(colour);
+            }
         }
     }
 
-    for row in 0..grid.len() {
-        for col in (0..grid[0].len()).rev() {
-            move_right(grid, row, col);
+    // the cycle start gets a red banner, later frames a red corner
+    if frame.cycle_start {
+        pixels[..width * BANNER].fill(5);
+    } else if in_cycle {
+        for y in 0..BANNER {
+            pixels[y * width + width.saturating_sub(BANNER)..(y + 1) * width].fill(5);
         }
     }
+
+    for (i, digit) in frame.load.to_string().bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        for bit in 0..15 {
+            let (x, y) = (2 + i * 4 + bit % 3, 2 + bit / 3);
+            if glyph >> (14 - bit) & 1 == 1 && x < width {
+                pixels[y * width + x] = 4;
+            }
+        }
+    }
+
+    pixels
+}
+
+/// animated GIF of the recorded frames, pausing on the cycle start
+#[allow(dead_code)]
+pub fn write_gif(input: &Input, writer: impl Write) -> Result<(), gif::EncodingError> {
+    let (platform, frames) = record_frames(input);
+    let width = (platform.width * CELL) as u16;
+    let height = (platform.height * CELL + BANNER) as u16;
+
+    let mut encoder = gif::Encoder::new(writer, width, height, &PALETTE)?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    let mut in_cycle = false;
+    for frame in &frames {
+        in_cycle |= frame.cycle_start;
+        let pixels = draw_frame(&platform, frame, in_cycle);
+        let mut gif_frame = gif::Frame::from_indexed_pixels(width, height, &pixels, None);
+        gif_frame.delay = if frame.cycle_start { 150 } else { 20 };
+        encoder.write_frame(&gif_frame)?;
+    }
+
+    Ok(())
 }
 
-fn support_beams(grid: &Input) -> usize {
-    grid.iter()
-        .rev()
-        .enumerate()
-        .map(|(i, row)| row.iter().filter(|char| *char == &'O').count() * (i + 1))
-        .sum()
+#[allow(dead_code)]
+pub fn save_gif(input: &Input, path: impl AsRef<Path>) -> io::Result<()> {
+    write_gif(input, BufWriter::new(File::create(path)?)).map_err(io::Error::other)
 }
 
 #[allow(dead_code)]
@@ -132,14 +361,6 @@ fn print_grid(grid: &Input) {
     println!(" ");
 }
 
-fn stringify(grid: &Input) -> String {
-    grid.iter()
-        .fold(String::from(""), |mut acc: String, row: &Vec<char>| {
-            acc.push_str(&row.iter().join(""));
-            acc
-        })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +385,64 @@ O.#..O.#.#
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 64);
     }
+
+    #[test]
+    fn test_tilt() {
+        let (platform, mut rocks) = Platform::new(&generator(SAMPLE));
+        platform.spin_cycle(&mut rocks);
+        assert_eq!(
+            platform.to_grid(&rocks),
+            generator(
+                ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#...."
+            )
+        );
+
+        platform.tilt(&mut rocks, Direction::West);
+        assert_eq!(
+            platform.to_grid(&rocks)[1],
+            "....#O...#".chars().collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_record_frames() {
+        let input = generator(SAMPLE);
+        let (platform, frames) = record_frames(&input);
+        let (start, rocks) = Platform::new(&input);
+        let (cycle, _) = cycle::detect(rocks, |rocks| {
+            let mut rocks = rocks.clone();
+            start.spin_cycle(&mut rocks);
+            rocks
+        });
+
+        assert_eq!(frames.len(), 1 + 4 * (cycle.mu + cycle.lambda));
+        assert_eq!(
+            frames
+                .iter()
+                .positions(|frame| frame.cycle_start)
+                .collect_vec(),
+            vec![4 * cycle.mu]
+        );
+        assert!(frames
+            .iter()
+            .all(|frame| frame.load == platform.north_load(&frame.rocks)));
+
+        let mut gif = Vec::new();
+        write_gif(&input, &mut gif).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+        let mut decoded = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            decoded += 1;
+        }
+        assert_eq!(decoded, frames.len());
+    }
 }
@@ -1,14 +1,133 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
 
 type Input<'a> = Vec<&'a [u8]>;
 
-#[derive(Debug, Clone)]
-struct Lens {
-    focal_length: usize,
-    order: usize,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lens<'a> {
+    pub label: &'a [u8],
+    pub focal_length: usize,
 }
 
-pub fn generator(input: &str) -> Input {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step<'a> {
+    Insert { label: &'a [u8], focal: usize },
+    Remove { label: &'a [u8] },
+}
+
+impl<'a> Step<'a> {
+    pub fn parse(step: &'a [u8]) -> Self {
+        match step.split_last() {
+            Some((&MINUS, label)) => Step::Remove { label },
+            _ => {
+                let mut split = step.split(|char| char == &EQUALS);
+                let label = split.next().unwrap();
+                let focal = split.next().unwrap_or_else(|| panic!("no focal length"));
+                Step::Insert {
+                    label,
+                    focal: std::str::from_utf8(focal).unwrap().parse().unwrap(),
+                }
+            }
+        }
+    }
+}
+
+/// The 256 boxes of the HASHMAP procedure, keeping lenses in insertion order.
+/// Removal leaves a gap that is swept out once gaps outnumber lenses in a box.
+#[derive(Debug)]
+pub struct LensBoxes<'a> {
+    boxes: Vec<Vec<Option<Lens<'a>>>>,
+    /// slot of each label within its box
+    slots: HashMap<&'a [u8], usize>,
+    gaps: Vec<usize>,
+}
+
+impl Default for LensBoxes<'_> {
+    fn default() -> Self {
+        LensBoxes {
+            boxes: vec![Vec::new(); 256],
+            slots: HashMap::new(),
+            gaps: vec![0; 256],
+        }
+    }
+}
+
+impl<'a> LensBoxes<'a> {
+    pub fn apply(&mut self, step: Step<'a>) {
+        match step {
+            Step::Insert { label, focal } => self.insert(label, focal),
+            Step::Remove { label } => {
+                self.remove(label);
+            }
+        }
+    }
+
+    /// replace the lens with this label in place, or add it to the back of its box
+    pub fn insert(&mut self, label: &'a [u8], focal_length: usize) {
+        let lenses = &mut self.boxes[hash(label)];
+        let lens = Some(Lens {
+            label,
+            focal_length,
+        });
+
+        match self.slots.get(label) {
+            Some(slot) => lenses[*slot] = lens,
+            None => {
+                self.slots.insert(label, lenses.len());
+                lenses.push(lens);
+            }
+        }
+    }
+
+    /// take out the lens with this label, returning its focal length
+    pub fn remove(&mut self, label: &[u8]) -> Option<usize> {
+        let box_num = hash(label);
+        let slot = self.slots.remove(label)?;
+        let removed = self.boxes[box_num][slot].take()?;
+
+        self.gaps[box_num] += 1;
+        if self.gaps[box_num] * 2 > self.boxes[box_num].len() {
+            self.compact(box_num);
+        }
+
+        Some(removed.focal_length)
+    }
+
+    fn compact(&mut self, box_num: usize) {
+        self.boxes[box_num].retain(Option::is_some);
+        for (slot, lens) in self.boxes[box_num].iter().flatten().enumerate() {
+            self.slots.insert(lens.label, slot);
+        }
+        self.gaps[box_num] = 0;
+    }
+
+    pub fn lenses(&self, box_num: usize) -> impl Iterator<Item = &Lens<'a>> {
+        self.boxes[box_num].iter().flatten()
+    }
+
+    /// every lens as (box, lens) in box order, then front to back
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Lens<'a>)> {
+        (0..self.boxes.len())
+            .flat_map(move |box_num| self.lenses(box_num).map(move |lens| (box_num, lens)))
+    }
+
+    pub fn focusing_power(&self) -> usize {
+        (0..self.boxes.len())
+            .map(|box_num| {
+                self.lenses(box_num)
+                    .enumerate()
+                    .map(|(slot, lens)| (box_num + 1) * (slot + 1) * lens.focal_length)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+pub fn generator(input: &str) -> Input<'_> {
     input.split(',').map(|each| each.as_bytes()).collect()
 }
 
@@ -20,73 +139,243 @@ const MINUS: u8 = b'-';
 const EQUALS: u8 = b'=';
 
 pub fn part2(input: &Input) -> usize {
-    let mut boxes: Vec<HashMap<&[u8], Lens>> = vec![HashMap::new(); 256];
+    let mut boxes = LensBoxes::default();
+    input.iter().for_each(|step| boxes.apply(Step::parse(step)));
 
-    input.iter().for_each(|step| {
-        let mut split = step.split(split_on);
-        let label: &[u8] = split.next().unwrap();
-        let focal_length = split.next().unwrap_or_default();
-        let box_num = hash(label);
+    boxes.focusing_power()
+}
+
+fn hash(chars: &[u8]) -> usize {
+    chars.iter().fold(0_usize, |acc, char| {
+        (acc + <u8 as Into<usize>>::into(*char)) * 17 % 256
+    })
+}
+
+/// the letters the puzzle's own labels are made of
+#[allow(dead_code)]
+pub const LOWERCASE: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+/// printable ASCII, minus the `=`, `-` and `,` that would break up a step
+#[allow(dead_code)]
+pub const ASCII: &[u8] =
+    b"!\"#$%&'()*+./0123456789:;<>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+/// Why labels can't be drawn from an alphabet for a box
+#[derive(Debug, PartialEq)]
+pub enum LabelError {
+    /// outside [`ASCII`], so not readable back as part of a step
+    InvalidChar(u8),
+    /// listed twice, which would repeat labels
+    DuplicateChar(u8),
+    /// no label from the alphabet hashes to this box
+    Unreachable(usize),
+}
 
-        if focal_length.is_empty() {
-            // minus
-            if let Some(removed) = boxes[box_num].remove(label) {
-                boxes[box_num]
-                    .iter_mut()
-                    .for_each(|lens: (&&[u8], &mut Lens)| {
-                        if lens.1.order > 1 && lens.1.order > removed.order {
-                            lens.1.order -= 1
-                        }
-                    })
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LabelError::InvalidChar(char) => {
+                write!(f, "'{}' can't appear in a label", char.escape_ascii())
             }
-        } else {
-            // equals
-            if let Some(existing) = boxes[box_num].get_mut(label) {
-                existing.focal_length = get_focal_length(focal_length);
-            } else {
-                let order = boxes[box_num].len() + 1;
-                boxes[box_num].insert(
-                    label,
-                    Lens {
-                        focal_length: get_focal_length(focal_length),
-                        order,
-                    },
-                );
+            LabelError::DuplicateChar(char) => {
+                write!(f, "'{}' appears twice in the alphabet", char.escape_ascii())
+            }
+            LabelError::Unreachable(box_num) => {
+                write!(f, "no label from the alphabet hashes to box {box_num}")
             }
         }
-    });
+    }
+}
 
-    // boxes.iter().enumerate().for_each(|(i, b)| if !b.is_empty() { println!("box: {} - lenses: {:?}", i, b); });
+fn check_alphabet(alphabet: &[u8]) -> Result<(), LabelError> {
+    for (i, char) in alphabet.iter().enumerate() {
+        if !ASCII.contains(char) {
+            return Err(LabelError::InvalidChar(*char));
+        }
+        if alphabet[..i].contains(char) {
+            return Err(LabelError::DuplicateChar(*char));
+        }
+    }
+    Ok(())
+}
+
+/// boxes some non-empty label from `alphabet` hashes to, by search over the 256 hashes
+fn reachable_boxes(alphabet: &[u8]) -> [bool; 256] {
+    let mut reached = [false; 256];
+    let mut queue = VecDeque::from([0]);
+    while let Some(h) = queue.pop_front() {
+        for char in alphabet {
+            let next = step_hash(h, *char);
+            if !reached[next] {
+                reached[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+/// every label of this length drawn from `alphabet` that hashes to the box, in alphabet order
+pub fn labels_for_box(
+    box_num: usize,
+    len: usize,
+    alphabet: &[u8],
+) -> Result<Vec<String>, LabelError> {
+    check_alphabet(alphabet)?;
+    if box_num >= 256 {
+        return Ok(Vec::new());
+    }
+    // reaches[k][h]: k more characters can take hash h to the box
+    let mut reaches = vec![[false; 256]];
+    reaches[0][box_num] = true;
+    for k in 1..=len {
+        let next: [bool; 256] = std::array::from_fn(|h| {
+            alphabet
+                .iter()
+                .any(|char| reaches[k - 1][step_hash(h, *char)])
+        });
+        reaches.push(next);
+    }
+
+    let mut labels = Vec::new();
+    let mut label = Vec::with_capacity(len);
+    extend_labels(&reaches, alphabet, 0, &mut label, &mut labels);
+    Ok(labels)
+}
+
+fn extend_labels(
+    reaches: &[[bool; 256]],
+    alphabet: &[u8],
+    h: usize,
+    label: &mut Vec<u8>,
+    labels: &mut Vec<String>,
+) {
+    let remaining = reaches.len() - 1 - label.len();
+    if !reaches[remaining][h] {
+        return;
+    }
+    if remaining == 0 {
+        labels.push(String::from_utf8(label.clone()).unwrap());
+        return;
+    }
+    for char in alphabet {
+        label.push(*char);
+        extend_labels(reaches, alphabet, step_hash(h, *char), label, labels);
+        label.pop();
+    }
+}
+
+fn step_hash(h: usize, char: u8) -> usize {
+    (h + char as usize) * 17 % 256
+}
+
+/// the shortest labels from `alphabet` that hash to the box, at most `count` of them,
+/// e.g. with [`ASCII`] for any label a step can hold or [`LOWERCASE`] for puzzle-like ones
+#[allow(dead_code)]
+pub fn shortest_labels(
+    box_num: usize,
+    count: usize,
+    alphabet: &[u8],
+) -> Result<Vec<String>, LabelError> {
+    check_reachable(box_num, alphabet)?;
+    for len in 1.. {
+        let labels = labels_for_box(box_num, len, alphabet)?;
+        if !labels.is_empty() {
+            return Ok(labels.into_iter().take(count).collect());
+        }
+    }
+    unreachable!("the box is reachable")
+}
 
-    boxes
-        .iter()
+/// each character permutes the hashes, so a reachable box has labels of endless lengths
+fn check_reachable(box_num: usize, alphabet: &[u8]) -> Result<(), LabelError> {
+    check_alphabet(alphabet)?;
+    match reachable_boxes(alphabet).get(box_num) {
+        Some(true) => Ok(()),
+        _ => Err(LabelError::Unreachable(box_num)),
+    }
+}
+
+/// an initialisation sequence that puts `lenses` different lenses in the same box,
+/// with labels drawn from `alphabet`
+#[allow(dead_code)]
+pub fn adversarial_sequence(
+    box_num: usize,
+    lenses: usize,
+    alphabet: &[u8],
+) -> Result<String, LabelError> {
+    check_reachable(box_num, alphabet)?;
+    Ok((1..)
+        .flat_map(|len| labels_for_box(box_num, len, alphabet).unwrap())
+        .take(lenses)
         .enumerate()
-        .fold(0, |acc: usize, (box_num, lenses)| {
-            acc + lenses.values().fold(0, |acc, lens| {
-                acc + (box_num + 1) * lens.order * lens.focal_length
-            })
-        })
+        .map(|(i, label)| format!("{label}={}", i % 9 + 1))
+        .collect::<Vec<_>>()
+        .join(","))
 }
 
-fn hash(chars: &[u8]) -> usize {
-    chars.iter().fold(0_usize, |acc, char| {
-        (acc + <u8 as Into<usize>>::into(*char)) * 17 % 256
-    })
+/// How the distinct labels of a sequence spread over the boxes
+#[allow(dead_code)]
+#[derive(Debug, PartialEq)]
+pub struct Distribution {
+    pub labels_per_box: Vec<usize>,
 }
 
-fn split_on(char: &u8) -> bool {
-    char == &MINUS || char == &EQUALS
+#[allow(dead_code)]
+impl Distribution {
+    pub fn new(input: &Input) -> Self {
+        let mut labels_per_box = vec![0; 256];
+        let labels = input.iter().map(|step| match Step::parse(step) {
+            Step::Insert { label, .. } | Step::Remove { label } => label,
+        });
+        for label in labels.collect::<HashSet<_>>() {
+            labels_per_box[hash(label)] += 1;
+        }
+        Distribution { labels_per_box }
+    }
+
+    pub fn busiest(&self) -> (usize, usize) {
+        let (box_num, count) = self
+            .labels_per_box
+            .iter()
+            .enumerate()
+            .max_by_key(|(box_num, count)| (**count, Reverse(*box_num)))
+            .unwrap();
+        (box_num, *count)
+    }
+
+    pub fn occupied(&self) -> usize {
+        self.labels_per_box
+            .iter()
+            .filter(|count| **count > 0)
+            .count()
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.labels_per_box.iter().sum::<usize>() as f64 / 256.0
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.labels_per_box
+            .iter()
+            .map(|count| (*count as f64 - mean).powi(2))
+            .sum::<f64>()
+            / 256.0
+    }
 }
 
-fn get_focal_length(focal_length: &[u8]) -> usize {
-    if focal_length.is_empty() {
-        panic!("no focal length")
-    } else {
-        // my god this is gross, find a better way
-        String::from_utf8(focal_length.to_vec())
-            .unwrap()
-            .parse::<usize>()
-            .unwrap()
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (busiest, most) = self.busiest();
+        writeln!(f, "labels: {}", self.labels_per_box.iter().sum::<usize>())?;
+        writeln!(f, "occupied boxes: {} / 256", self.occupied())?;
+        writeln!(f, "busiest box: {busiest} with {most} labels")?;
+        write!(
+            f,
+            "labels per box: mean {:.2}, variance {:.2}",
+            self.mean(),
+            self.variance()
+        )
     }
 }
 
@@ -105,4 +394,91 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 145);
     }
+
+    #[test]
+    fn test_lens_boxes() {
+        let mut boxes = LensBoxes::default();
+        for step in generator(SAMPLE) {
+            boxes.apply(Step::parse(step));
+        }
+        assert_eq!(
+            boxes
+                .iter()
+                .map(|(box_num, lens)| (box_num, lens.label, lens.focal_length))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, &b"rn"[..], 1),
+                (0, b"cm", 2),
+                (3, b"ot", 7),
+                (3, b"ab", 5),
+                (3, b"pc", 6)
+            ]
+        );
+
+        assert_eq!(boxes.remove(b"rn"), Some(1));
+        assert_eq!(boxes.remove(b"rn"), None);
+        boxes.insert(b"rn", 4);
+        assert_eq!(
+            boxes.lenses(0).map(|lens| lens.label).collect::<Vec<_>>(),
+            vec![&b"cm"[..], b"rn"]
+        );
+    }
+
+    #[test]
+    fn test_hash_tooling() {
+        assert_eq!(
+            shortest_labels(0, 10, LOWERCASE),
+            Ok(vec!["cm".into(), "rn".into()])
+        );
+        // uppercase reaches some boxes in fewer characters
+        assert_eq!(shortest_labels(81, 10, ASCII), Ok(vec!["A".into()]));
+        assert_eq!(shortest_labels(81, 1, LOWERCASE).unwrap()[0].len(), 2);
+        let labels = labels_for_box(200, 3, LOWERCASE).unwrap();
+        assert!(labels.iter().all(|label| hash(label.as_bytes()) == 200));
+        assert!(labels.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let sequence = adversarial_sequence(42, 1000, ASCII).unwrap();
+        let input = generator(&sequence);
+        let distribution = Distribution::new(&input);
+        assert_eq!(distribution.busiest(), (42, 1000));
+        assert_eq!(distribution.occupied(), 1);
+
+        let distribution = Distribution::new(&generator(SAMPLE));
+        assert_eq!(distribution.busiest(), (3, 3));
+        assert_eq!(distribution.to_string().lines().next(), Some("labels: 6"));
+    }
+
+    #[test]
+    fn test_label_errors() {
+        // even characters keep the hash even, so odd boxes are out of reach
+        assert_eq!(labels_for_box(1, 5, b"02468"), Ok(vec![]));
+        assert_eq!(
+            shortest_labels(1, 1, b"02468"),
+            Err(LabelError::Unreachable(1))
+        );
+        assert!(shortest_labels(2, 1, b"02468").is_ok());
+        assert_eq!(
+            adversarial_sequence(0, 10, b""),
+            Err(LabelError::Unreachable(0))
+        );
+        assert_eq!(
+            shortest_labels(256, 1, ASCII),
+            Err(LabelError::Unreachable(256))
+        );
+
+        for char in [b'=', b'-', b',', b' ', b'\n', 0x80, 0xff] {
+            assert_eq!(
+                shortest_labels(0, 1, &[b'a', char]),
+                Err(LabelError::InvalidChar(char))
+            );
+        }
+        assert_eq!(
+            adversarial_sequence(0, 1, b"aba"),
+            Err(LabelError::DuplicateChar(b'a'))
+        );
+        assert_eq!(
+            LabelError::InvalidChar(0x80).to_string(),
+            "'\\x80' can't appear in a label"
+        );
+    }
 }
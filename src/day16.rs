@@ -1,26 +1,58 @@
-use std::collections::HashSet;
-
 use grid::Grid;
 use itertools::Itertools;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
 
 type Input = Grid<char>;
 
-#[derive(Debug)]
-struct Beam {
-    direction: Direction,
-    position: Coord,
-}
-
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-enum Direction {
+pub enum Direction {
     Left,
     Right,
     Up,
     Down,
 }
 
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// the directions set in a mask of `bit`s
+    fn each(mask: u8) -> impl Iterator<Item = Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(move |direction| mask & direction.bit() != 0)
+    }
+
+    fn clockwise(self) -> Direction {
+        match self {
+            Direction::Left => Direction::Up,
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        self.clockwise().clockwise()
+    }
+
+    fn counter_clockwise(self) -> Direction {
+        self.clockwise().clockwise().clockwise()
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
-struct Coord {
+pub struct Coord {
     row: usize,
     col: usize,
 }
@@ -36,160 +68,489 @@ pub fn generator(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> usize {
-    main(0, 0, Direction::Right, input)
+    let contraption = Contraption::new(input, &Optics::standard()).unwrap();
+    contraption.energized(Coord { row: 0, col: 0 }, Direction::Right)
 }
 
 pub fn part2(input: &Input) -> usize {
-    let cols = 0..input.cols();
-    let rows = 0..input.rows();
+    let contraption = Contraption::new(input, &Optics::standard()).unwrap();
+    let graph = BeamGraph::new(&contraption);
 
-    let left = rows
-        .clone()
-        .map(|row| main(row, input.cols() - 1, Direction::Left, input))
-        .max()
-        .unwrap();
-    let right = rows
-        .map(|row| main(row, 0, Direction::Right, input))
-        .max()
-        .unwrap();
-    let up = cols
-        .clone()
-        .map(|col| main(input.rows() - 1, col, Direction::Up, input))
+    edge_entries(input)
+        .par_iter()
+        .map(|(start, direction)| graph.energized(*start, *direction))
         .max()
-        .unwrap();
-    let down = cols
-        .map(|col| main(0, col, Direction::Down, input))
-        .max()
-        .unwrap();
+        .unwrap()
+}
 
-    *[left, right, up, down].iter().max().unwrap()
+/// An optical element a beam can pass through
+pub trait Optic {
+    /// mask of directions leaving the tile for a beam entering travelling `direction`
+    fn bend(&self, direction: Direction) -> u8;
 }
 
-fn main(row: usize, col: usize, direction: Direction, input: &Input) -> usize {
-    let mut engergized_grid = Grid::init(input.rows(), input.cols(), HashSet::with_capacity(4));
+/// `.`
+pub struct Empty;
+
+impl Optic for Empty {
+    fn bend(&self, direction: Direction) -> u8 {
+        direction.bit()
+    }
+}
 
-    let mut start = Beam {
-        position: Coord { row, col },
-        direction,
-    };
-    start.direction = bend_light(&start, &input[(row, col)])[0];
+/// `/` and `\`
+pub struct Mirror {
+    pub forward: bool,
+}
 
-    move_beam(input, &mut engergized_grid, start);
+impl Optic for Mirror {
+    fn bend(&self, direction: Direction) -> u8 {
+        let out = match direction {
+            Direction::Left | Direction::Right => direction.counter_clockwise(),
+            Direction::Up | Direction::Down => direction.clockwise(),
+        };
+        match self.forward {
+            true => out.bit(),
+            false => out.opposite().bit(),
+        }
+    }
+}
 
-    engergized_grid
-        .iter()
-        .fold(0, |acc, tile| acc + if !tile.is_empty() { 1 } else { 0 })
+/// `-` and `|`
+pub struct Splitter {
+    pub horizontal: bool,
 }
 
-fn move_beam(input: &Input, grid: &mut Grid<HashSet<Direction>>, beam: Beam) {
-    if grid[(beam.position.row, beam.position.col)].contains(&beam.direction) {
-        return;
+impl Optic for Splitter {
+    fn bend(&self, direction: Direction) -> u8 {
+        match (self.horizontal, direction) {
+            (true, Direction::Left | Direction::Right) => direction.bit(),
+            (true, _) => Direction::Left.bit() | Direction::Right.bit(),
+            (false, Direction::Up | Direction::Down) => direction.bit(),
+            (false, _) => Direction::Up.bit() | Direction::Down.bit(),
+        }
     }
-    grid[(beam.position.row, beam.position.col)].insert(beam.direction);
+}
 
-    if let Some(next) = next_tile(&beam, input) {
-        let directions = bend_light(&beam, &input[(next.row, next.col)]);
+/// One-way: passes beams travelling in its direction and absorbs the rest
+pub struct Diode(pub Direction);
 
-        directions.iter().for_each(|direction| {
-            move_beam(
-                input,
-                grid,
-                Beam {
-                    direction: *direction,
-                    position: next,
-                },
-            )
-        });
+impl Optic for Diode {
+    fn bend(&self, direction: Direction) -> u8 {
+        match direction == self.0 {
+            true => direction.bit(),
+            false => 0,
+        }
     }
 }
 
-fn next_tile(beam: &Beam, input: &Input) -> Option<Coord> {
-    match beam.direction {
-        Direction::Left => {
-            if beam.position.col > 0 {
-                Some(Coord {
-                    col: beam.position.col - 1,
-                    row: beam.position.row,
-                })
-            } else {
-                None
+/// Swallows every beam
+pub struct Absorber;
+
+impl Optic for Absorber {
+    fn bend(&self, _: Direction) -> u8 {
+        0
+    }
+}
+
+/// Turns every beam a quarter turn
+pub struct Rotator {
+    pub clockwise: bool,
+}
+
+impl Optic for Rotator {
+    fn bend(&self, direction: Direction) -> u8 {
+        match self.clockwise {
+            true => direction.clockwise().bit(),
+            false => direction.counter_clockwise().bit(),
+        }
+    }
+}
+
+/// Merges beams from every side into one leaving in its direction
+pub struct Combiner(pub Direction);
+
+impl Optic for Combiner {
+    fn bend(&self, _: Direction) -> u8 {
+        self.0.bit()
+    }
+}
+
+/// The optical elements each tile symbol stands for
+pub struct Optics(HashMap<char, Box<dyn Optic>>);
+
+impl Optics {
+    /// the puzzle's `. / \ - |`
+    pub fn standard() -> Self {
+        let mut optics = Optics(HashMap::new());
+        optics.register('.', Empty);
+        optics.register('/', Mirror { forward: true });
+        optics.register('\\', Mirror { forward: false });
+        optics.register('-', Splitter { horizontal: true });
+        optics.register('|', Splitter { horizontal: false });
+        optics
+    }
+
+    /// standard plus diodes `< > ^ v`, absorber `#`, rotators `R` (clockwise) and `L`,
+    /// and combiners `W E N S` named for the compass point they send beams towards
+    #[allow(dead_code)]
+    pub fn extended() -> Self {
+        let mut optics = Optics::standard();
+        let arrows = ['<', '>', '^', 'v'];
+        let compass = ['W', 'E', 'N', 'S'];
+        for (direction, (arrow, point)) in Direction::ALL
+            .into_iter()
+            .zip(arrows.into_iter().zip(compass))
+        {
+            optics.register(arrow, Diode(direction));
+            optics.register(point, Combiner(direction));
+        }
+        optics.register('#', Absorber);
+        optics.register('R', Rotator { clockwise: true });
+        optics.register('L', Rotator { clockwise: false });
+        optics
+    }
+
+    pub fn register(&mut self, symbol: char, optic: impl Optic + 'static) {
+        self.0.insert(symbol, Box::new(optic));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownTile {
+    pub tile: char,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for UnknownTile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown tile type {:?} at row {}, col {}",
+            self.tile, self.row, self.col
+        )
+    }
+}
+
+/// What a single beam did on its way through the contraption
+#[derive(Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub energized: usize,
+    /// cycles beams ended up going round
+    pub loops: usize,
+    /// beams leaving through each edge, indexed by the direction they left in
+    pub exits: [usize; 4],
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "energized tiles: {}", self.energized)?;
+        writeln!(f, "loops: {}", self.loops)?;
+        let [left, right, top, bottom] = self.exits;
+        write!(
+            f,
+            "exits: left {left}, right {right}, top {top}, bottom {bottom}"
+        )
+    }
+}
+
+/// A grid of optics, with each tile's bends looked up ahead of time
+pub struct Contraption {
+    /// output mask per tile, indexed by the direction a beam entered in
+    bends: Grid<[u8; 4]>,
+}
+
+impl Contraption {
+    pub fn new(input: &Input, optics: &Optics) -> Result<Self, UnknownTile> {
+        let mut bends = Grid::new(input.rows(), input.cols());
+        for ((row, col), tile) in input.indexed_iter() {
+            let optic = optics.0.get(tile).ok_or(UnknownTile {
+                tile: *tile,
+                row,
+                col,
+            })?;
+            bends[(row, col)] = Direction::ALL.map(|direction| optic.bend(direction));
+        }
+        Ok(Contraption { bends })
+    }
+
+    fn rows(&self) -> usize {
+        self.bends.rows()
+    }
+
+    fn cols(&self) -> usize {
+        self.bends.cols()
+    }
+
+    fn state(&self, position: Coord, direction: Direction) -> usize {
+        (position.row * self.cols() + position.col) * 4 + direction as usize
+    }
+
+    /// where a beam entering `position` goes next, `None` when it leaves the grid
+    fn outputs(
+        &self,
+        position: Coord,
+        direction: Direction,
+    ) -> impl Iterator<Item = (Direction, Option<Coord>)> + '_ {
+        let mask = self.bends[(position.row, position.col)][direction as usize];
+        Direction::each(mask).map(move |out| (out, next_tile(position, out, self)))
+    }
+
+    /// the states each (tile, direction) state leads to
+    fn transitions(&self) -> Vec<[u32; 4]> {
+        let mut next = vec![[NONE; 4]; self.rows() * self.cols() * 4];
+        for (row, col) in (0..self.rows()).cartesian_product(0..self.cols()) {
+            let position = Coord { row, col };
+            for direction in Direction::ALL {
+                let outputs = self
+                    .outputs(position, direction)
+                    .filter_map(|(out, to)| to.map(|to| self.state(to, out) as u32));
+                for (slot, to) in outputs.enumerate() {
+                    next[self.state(position, direction)][slot] = to;
+                }
             }
         }
-        Direction::Right => {
-            if beam.position.col < input.cols() - 1 {
-                Some(Coord {
-                    col: beam.position.col + 1,
-                    row: beam.position.row,
-                })
-            } else {
-                None
+        next
+    }
+
+    /// the directions each tile was entered in, and beams leaving per edge
+    fn trace(&self, start: Coord, direction: Direction) -> (Grid<u8>, [usize; 4]) {
+        let mut entered: Grid<u8> = Grid::new(self.rows(), self.cols());
+        let mut exits = [0; 4];
+        let mut beams = vec![(start, direction)];
+
+        while let Some((position, direction)) = beams.pop() {
+            let tile = &mut entered[(position.row, position.col)];
+            if *tile & direction.bit() != 0 {
+                continue;
+            }
+            *tile |= direction.bit();
+
+            for (out, next) in self.outputs(position, direction) {
+                match next {
+                    Some(next) => beams.push((next, out)),
+                    None => exits[out as usize] += 1,
+                }
             }
         }
-        Direction::Up => {
-            if beam.position.row > 0 {
-                Some(Coord {
-                    col: beam.position.col,
-                    row: beam.position.row - 1,
-                })
-            } else {
-                None
+
+        (entered, exits)
+    }
+
+    /// tiles lit by a beam entering `start` travelling in `direction`
+    pub fn energized(&self, start: Coord, direction: Direction) -> usize {
+        let (entered, _) = self.trace(start, direction);
+        entered.iter().filter(|tile| **tile != 0).count()
+    }
+
+    #[allow(dead_code)]
+    pub fn run(&self, start: Coord, direction: Direction) -> Stats {
+        let (entered, exits) = self.trace(start, direction);
+        let (component, count) = strongly_connected(&self.transitions());
+
+        // everything downstream of a lit state is lit, so whole components are
+        let mut sizes = vec![0; count];
+        for (tile, mask) in entered.iter().enumerate() {
+            for direction in Direction::each(*mask) {
+                sizes[component[tile * 4 + direction as usize] as usize] += 1;
+            }
+        }
+
+        Stats {
+            energized: entered.iter().filter(|tile| **tile != 0).count(),
+            loops: sizes.iter().filter(|size| **size > 1).count(),
+            exits,
+        }
+    }
+}
+
+/// every way a beam can enter from outside the grid
+fn edge_entries(input: &Input) -> Vec<(Coord, Direction)> {
+    let (rows, cols) = (input.rows(), input.cols());
+    let left = (0..rows).map(|row| (Coord { row, col: 0 }, Direction::Right));
+    let right = (0..rows).map(|row| (Coord { row, col: cols - 1 }, Direction::Left));
+    let down = (0..cols).map(|col| (Coord { row: 0, col }, Direction::Down));
+    let up = (0..cols).map(|col| (Coord { row: rows - 1, col }, Direction::Up));
+
+    left.chain(right).chain(down).chain(up).collect()
+}
+
+const NONE: u32 = u32::MAX;
+
+/// Every (tile, direction) a beam can enter in, condensed into strongly connected
+/// components so the tiles reachable from shared components are only unioned once
+#[derive(Debug)]
+struct BeamGraph {
+    cols: usize,
+    words: usize,
+    component: Vec<u32>,
+    tiles: Vec<Vec<u32>>,
+    successors: Vec<Vec<u32>>,
+    /// reachable tiles, kept for components entered from more than one place
+    shared: Vec<Option<Vec<u64>>>,
+}
+
+impl BeamGraph {
+    fn new(contraption: &Contraption) -> Self {
+        let (rows, cols) = (contraption.rows(), contraption.cols());
+        let next = contraption.transitions();
+
+        let (component, count) = strongly_connected(&next);
+
+        let mut tiles = vec![Vec::new(); count];
+        let mut successors = vec![Vec::new(); count];
+        for (from, to) in next.iter().enumerate() {
+            let c = component[from] as usize;
+            tiles[c].push(from as u32 / 4);
+            for to in to.iter().filter(|to| **to != NONE) {
+                if component[*to as usize] as usize != c {
+                    successors[c].push(component[*to as usize]);
+                }
+            }
+        }
+        let mut entries = vec![0; count];
+        for c in 0..count {
+            tiles[c].sort_unstable();
+            tiles[c].dedup();
+            successors[c].sort_unstable();
+            successors[c].dedup();
+            for to in &successors[c] {
+                entries[*to as usize] += 1;
             }
         }
-        Direction::Down => {
-            if beam.position.row < input.rows() - 1 {
-                Some(Coord {
-                    col: beam.position.col,
-                    row: beam.position.row + 1,
-                })
+
+        let mut graph = BeamGraph {
+            cols,
+            words: (rows * cols).div_ceil(64),
+            component,
+            tiles,
+            successors,
+            shared: vec![None; count],
+        };
+        // components are numbered downstream first, so shared ones below are ready
+        for (c, entries) in entries.into_iter().enumerate() {
+            if entries > 1 {
+                graph.shared[c] = Some(graph.reachable(c));
+            }
+        }
+
+        graph
+    }
+
+    /// tiles reachable from a component, reusing shared components downstream
+    fn reachable(&self, from: usize) -> Vec<u64> {
+        let mut lit = vec![0; self.words];
+        let mut merged = vec![false; self.tiles.len()];
+        let mut stack = vec![from];
+
+        while let Some(c) = stack.pop() {
+            match &self.shared[c] {
+                Some(shared) if c != from => {
+                    if !merged[c] {
+                        merged[c] = true;
+                        lit.iter_mut().zip(shared).for_each(|(a, b)| *a |= b);
+                    }
+                }
+                _ => {
+                    for tile in &self.tiles[c] {
+                        lit[*tile as usize / 64] |= 1 << (tile % 64);
+                    }
+                    stack.extend(self.successors[c].iter().map(|to| *to as usize));
+                }
+            }
+        }
+
+        lit
+    }
+
+    fn energized(&self, start: Coord, direction: Direction) -> usize {
+        let state = (start.row * self.cols + start.col) * 4 + direction as usize;
+        let c = self.component[state] as usize;
+        let lit = match &self.shared[c] {
+            Some(shared) => shared.clone(),
+            None => self.reachable(c),
+        };
+        lit.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// iterative Tarjan, numbering components so edges only run to lower numbers
+fn strongly_connected(next: &[[u32; 4]]) -> (Vec<u32>, usize) {
+    let n = next.len();
+    let mut index = vec![NONE; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut component = vec![NONE; n];
+    let (mut counter, mut count) = (0, 0);
+
+    for root in 0..n {
+        if index[root] != NONE {
+            continue;
+        }
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        let mut calls = vec![(root, 0)];
+
+        while let Some((v, slot)) = calls.pop() {
+            if slot < 4 {
+                calls.push((v, slot + 1));
+                let w = next[v][slot];
+                if w == NONE {
+                    continue;
+                }
+                let w = w as usize;
+                if index[w] == NONE {
+                    index[w] = counter;
+                    low[w] = counter;
+                    counter += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
             } else {
-                None
+                if low[v] == index[v] {
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component[w] = count;
+                        if w == v {
+                            break;
+                        }
+                    }
+                    count += 1;
+                }
+                if let Some((parent, _)) = calls.last() {
+                    low[*parent] = low[*parent].min(low[v]);
+                }
             }
         }
     }
+
+    (component, count as usize)
 }
 
-fn bend_light(beam: &Beam, tile: &char) -> Vec<Direction> {
-    match tile {
-        '.' => vec![beam.direction],
-        '/' => match beam.direction {
-            Direction::Left => vec![Direction::Down],
-            Direction::Right => vec![Direction::Up],
-            Direction::Up => vec![Direction::Right],
-            Direction::Down => vec![Direction::Left],
-        },
-        '\\' => match beam.direction {
-            Direction::Left => vec![Direction::Up],
-            Direction::Right => vec![Direction::Down],
-            Direction::Up => vec![Direction::Left],
-            Direction::Down => vec![Direction::Right],
-        },
-        '-' => match beam.direction {
-            Direction::Left | Direction::Right => vec![beam.direction],
-            Direction::Up | Direction::Down => vec![Direction::Left, Direction::Right],
-        },
-        '|' => match beam.direction {
-            Direction::Up | Direction::Down => vec![beam.direction],
-            Direction::Left | Direction::Right => vec![Direction::Up, Direction::Down],
-        },
-        _ => panic!("unknown tile type: {} at {:?}", tile, beam.position),
-    }
-}
-
-#[allow(dead_code)]
-fn print_grid(grid: &Grid<HashSet<Direction>>) {
-    for row in 0..grid.rows() {
-        println!(
-            "{:?}",
-            grid.iter_row(row)
-                .map(|tile| if tile.is_empty() { '.' } else { '#' })
-                .join("")
-        );
+fn next_tile(position: Coord, direction: Direction, contraption: &Contraption) -> Option<Coord> {
+    let Coord { row, col } = position;
+    match direction {
+        Direction::Left if col > 0 => Some(Coord { row, col: col - 1 }),
+        Direction::Right if col < contraption.cols() - 1 => Some(Coord { row, col: col + 1 }),
+        Direction::Up if row > 0 => Some(Coord { row: row - 1, col }),
+        Direction::Down if row < contraption.rows() - 1 => Some(Coord { row: row + 1, col }),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
 
     const SAMPLE: &str = r".|...\....
 |.-.\.....
@@ -211,4 +572,82 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(&generator(SAMPLE)), 51);
     }
+
+    #[test]
+    fn test_beam_graph() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(16);
+        let random = (0..40)
+            .map(|_| {
+                (0..40)
+                    .map(|_| {
+                        *['.', '.', '.', '/', '\\', '-', '|']
+                            .choose(&mut rng)
+                            .unwrap()
+                    })
+                    .collect::<String>()
+            })
+            .join("\n");
+
+        for input in [generator(SAMPLE), generator(&random)] {
+            let contraption = Contraption::new(&input, &Optics::standard()).unwrap();
+            let graph = BeamGraph::new(&contraption);
+            for (start, direction) in edge_entries(&input) {
+                assert_eq!(
+                    graph.energized(start, direction),
+                    contraption.energized(start, direction)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let contraption = Contraption::new(&generator(SAMPLE), &Optics::standard()).unwrap();
+        assert_eq!(
+            contraption.run(Coord { row: 0, col: 0 }, Direction::Right),
+            Stats {
+                energized: 46,
+                loops: 1,
+                exits: [1, 1, 2, 3],
+            }
+        );
+    }
+
+    #[test]
+    fn test_extended_optics() {
+        let run = |input: &str, start: Coord, direction: Direction| {
+            Contraption::new(&generator(input), &Optics::extended())
+                .unwrap()
+                .run(start, direction)
+        };
+        let origin = Coord { row: 0, col: 0 };
+
+        // the right-facing diode lets the beam through, the left-facing one stops it
+        let stats = run(">.<", origin, Direction::Right);
+        assert_eq!((stats.energized, stats.exits), (3, [0; 4]));
+
+        // round the rotators and combined back north to leave on the right
+        let stats = run(".R\n#N", origin, Direction::Right);
+        assert_eq!((stats.energized, stats.exits), (3, [0, 1, 0, 0]));
+
+        // the rotators send the beam back into the splitter, which keeps one half circling
+        let stats = run("-R\nRR", origin, Direction::Right);
+        assert_eq!(
+            stats,
+            Stats {
+                energized: 4,
+                loops: 1,
+                exits: [1, 0, 0, 0],
+            }
+        );
+
+        assert_eq!(
+            Contraption::new(&generator("..\n.?"), &Optics::standard()).err(),
+            Some(UnknownTile {
+                tile: '?',
+                row: 1,
+                col: 1
+            })
+        );
+    }
 }
@@ -1,139 +1,165 @@
 use itertools::Itertools;
-use pathfinding::{
-    num_traits::ToPrimitive,
-    prelude::{astar, Matrix},
-};
-use std::hash::{Hash, Hasher};
+use pathfinding::prelude::Matrix;
+use std::{fs, io, path::Path};
 
 type Input = Matrix<u32>;
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct Coord {
-    row: usize,
-    col: usize,
-}
-impl Coord {
-    fn direction(&self, destination: (usize, usize)) -> Direction {
-        let row_diff: isize = self.row.to_isize().unwrap() - destination.0.to_isize().unwrap();
-        let col_diff: isize = self.col.to_isize().unwrap() - destination.1.to_isize().unwrap();
+const NONE: usize = usize::MAX;
 
-        if row_diff > 0 {
-            return Direction::Up;
-        }
-        if row_diff < 0 {
-            return Direction::Down;
-        }
-        if col_diff > 0 {
-            return Direction::Left;
-        }
-        if col_diff < 0 {
-            return Direction::Right;
-        }
-        panic!(
-            "invalid direction - source: {:?}, destination: {:?}",
-            self, destination
-        );
-    }
+/// How far a crucible has to and may go in a straight line, and what turning costs
+#[derive(Debug, Clone, Copy)]
+pub struct Crucible {
+    pub min_run: usize,
+    pub max_run: usize,
+    pub turn_cost: u32,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-    Start,
+/// A cheapest way across, every block from the top left to the bottom right
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// heat loss plus turn costs
+    #[allow(dead_code)]
+    pub cost: u32,
+    pub heat_loss: u32,
+    pub path: Vec<(usize, usize)>,
 }
 
-#[derive(Debug, Clone, Eq)]
-struct Block {
-    coord: Coord,
-    blocks: usize,
-    direction: Direction,
-}
-impl PartialEq for Block {
-    fn eq(&self, other: &Self) -> bool {
-        self.coord == other.coord
-            && self.direction == other.direction
-            && self.blocks == other.blocks
+impl Route {
+    /// where the route starts, turns and ends, with the heat lost getting there
+    pub fn corners(&self, grid: &Input) -> Vec<((usize, usize), u32)> {
+        let mut heat_loss = 0;
+        let mut corners = vec![(self.path[0], 0)];
+        for (before, block, after) in self.path.iter().tuple_windows() {
+            heat_loss += grid[*block];
+            if before.0.abs_diff(after.0) == 1 {
+                corners.push((*block, heat_loss));
+            }
+        }
+        corners.push((*self.path.last().unwrap(), self.heat_loss));
+        corners
     }
-}
-impl Hash for Block {
-    fn hash<H: Hasher>(&self, hasher: &mut H) {
-        self.coord.hash(hasher);
-        self.direction.hash(hasher);
-        self.blocks.hash(hasher);
+
+    #[allow(dead_code)]
+    pub fn turns(&self) -> usize {
+        self.path
+            .iter()
+            .tuple_windows()
+            .map(|(a, b)| (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize))
+            .tuple_windows()
+            .filter(|(a, b)| a != b)
+            .count()
     }
 }
 
-struct Map {
-    grid: Matrix<u32>,
-    start: Block,
-    end: Coord,
-}
-impl Map {
-    fn new(input: &Input) -> Self {
-        let grid = input.clone();
-        let start = Block {
-            coord: Coord { row: 0, col: 0 },
-            blocks: 0,
-            direction: Direction::Start,
-        };
-        let end = Coord {
-            row: input.rows - 1,
-            col: input.columns - 1,
-        };
+impl Crucible {
+    pub const REGULAR: Crucible = Crucible {
+        min_run: 1,
+        max_run: 3,
+        turn_cost: 0,
+    };
 
-        Self { grid, start, end }
-    }
+    pub const ULTRA: Crucible = Crucible {
+        min_run: 4,
+        max_run: 10,
+        turn_cost: 0,
+    };
 
-    fn eligible_neighbors(
-        &self,
-        block: &Block,
-        filter_by: fn(&(Block, u32), &Block) -> bool,
-    ) -> Vec<(Block, u32)> {
-        self.grid
-            .neighbours((block.coord.row, block.coord.col), false)
-            .map(|neighbor| {
-                let direction = block.coord.direction(neighbor);
-                let blocks = if block.direction == direction {
-                    block.blocks + 1
-                } else {
-                    1
+    /// Dijkstra over (block, axis arrived on) with a bucket queue, each move a whole
+    /// straight run ending in a turn
+    pub fn solve(&self, grid: &Input) -> Option<Route> {
+        let (rows, cols) = (grid.rows, grid.columns);
+        let end = rows * cols - 1;
+        let mut cost = vec![u32::MAX; rows * cols * 2];
+        let mut previous = vec![NONE; rows * cols * 2];
+
+        // no move costs more than the widest bucket span, so the buckets can wrap around
+        let heaviest = grid.iter().flatten().max().copied().unwrap_or(0);
+        let width = (heaviest * self.max_run as u32 + self.turn_cost) as usize + 1;
+        let mut buckets = vec![Vec::new(); width];
+
+        // axis 0 arrived horizontally and moves off vertically, axis 1 the other way
+        cost[..2].fill(0);
+        buckets[0].extend([0, 1]);
+        let mut pending = 2;
+
+        let mut current = 0;
+        while pending > 0 {
+            while let Some(state) = buckets[current as usize % width].pop() {
+                pending -= 1;
+                if cost[state] != current {
+                    continue;
+                }
+                let (block, axis) = (state / 2, state % 2);
+                if block == end {
+                    return Some(self.route(grid, &previous, state, current));
+                }
+
+                let turn = match previous[state] {
+                    NONE => 0,
+                    _ => self.turn_cost,
                 };
+                let (row, col) = (block / cols, block % cols);
+                for sign in [-1, 1] {
+                    let (d_row, d_col) = match axis {
+                        0 => (sign, 0),
+                        _ => (0, sign),
+                    };
+                    let mut heat = 0;
+                    for step in 1..=self.max_run as isize {
+                        let (Some(row), Some(col)) = (
+                            row.checked_add_signed(d_row * step)
+                                .filter(|row| *row < rows),
+                            col.checked_add_signed(d_col * step)
+                                .filter(|col| *col < cols),
+                        ) else {
+                            break;
+                        };
+                        heat += grid[(row, col)];
+                        if (step as usize) < self.min_run {
+                            continue;
+                        }
 
-                (
-                    Block {
-                        coord: Coord {
-                            row: neighbor.0,
-                            col: neighbor.1,
-                        },
-                        blocks,
-                        direction,
-                    },
-                    self.grid[neighbor], // heat loss
-                )
-            })
-            .filter(|neighbor| {
-                filter_by(neighbor, block)
-                    && match block.direction {
-                        Direction::Down => neighbor.0.direction != Direction::Up,
-                        Direction::Up => neighbor.0.direction != Direction::Down,
-                        Direction::Left => neighbor.0.direction != Direction::Right,
-                        Direction::Right => neighbor.0.direction != Direction::Left,
-                        Direction::Start => {
-                            [Direction::Down, Direction::Right].contains(&neighbor.0.direction)
+                        let next = (row * cols + col) * 2 + (1 - axis);
+                        let next_cost = current + heat + turn;
+                        if next_cost < cost[next] {
+                            cost[next] = next_cost;
+                            previous[next] = state;
+                            buckets[next_cost as usize % width].push(next);
+                            pending += 1;
                         }
                     }
-            })
-            .collect()
+                }
+            }
+            current += 1;
+        }
+
+        None
     }
 
-    fn heuristic(&self, block: &Block) -> u32 {
-        let a = self.end.row.abs_diff(block.coord.row);
-        let b = self.end.col.abs_diff(block.coord.col);
-        // an actual use for the pythagorean theorom lol
-        f32::sqrt((a.pow(2) + b.pow(2)) as f32).to_u32().unwrap()
+    fn route(&self, grid: &Input, previous: &[usize], mut state: usize, cost: u32) -> Route {
+        let cols = grid.columns;
+        let mut corners = vec![state / 2];
+        while previous[state] != NONE {
+            state = previous[state];
+            corners.push(state / 2);
+        }
+
+        let mut path = vec![(0, 0)];
+        for (from, to) in corners.iter().rev().tuple_windows() {
+            let (mut row, mut col) = (from / cols, from % cols);
+            let (to_row, to_col) = (to / cols, to % cols);
+            while (row, col) != (to_row, to_col) {
+                row = (row as isize + (to_row as isize - row as isize).signum()) as usize;
+                col = (col as isize + (to_col as isize - col as isize).signum()) as usize;
+                path.push((row, col));
+            }
+        }
+
+        Route {
+            cost,
+            heat_loss: path.iter().skip(1).map(|block| grid[*block]).sum(),
+            path,
+        }
     }
 }
 
@@ -147,71 +173,99 @@ pub fn generator(input: &str) -> Input {
 }
 
 pub fn part1(input: &Input) -> u32 {
-    let map = Map::new(input);
-
-    let filter_by = |neighbor: &(Block, u32), _block: &Block| -> bool { neighbor.0.blocks <= 3 };
+    Crucible::REGULAR
+        .solve(input)
+        .unwrap_or_else(|| panic!("no path found"))
+        .heat_loss
+}
 
-    let path = astar(
-        &map.start,
-        |block| map.eligible_neighbors(block, filter_by),
-        |block| map.heuristic(block),
-        |block| map.end.eq(&block.coord),
-    )
-    .unwrap_or_else(|| panic!("no path found"));
+pub fn part2(input: &Input) -> u32 {
+    Crucible::ULTRA
+        .solve(input)
+        .unwrap_or_else(|| panic!("no path found"))
+        .heat_loss
+}
 
-    // print_grid(&map.grid, &path.0);
-    path.1
+/// hex colour from pale yellow for the coolest block to deep red for the hottest
+fn heat_colour(heat: u32, coolest: u32, hottest: u32) -> String {
+    let t = (heat - coolest) as f32 / (hottest - coolest).max(1) as f32;
+    let [r, g, b] = [(255, 176), (245, 16), (208, 16)]
+        .map(|(low, high)| (low as f32 + (high as f32 - low as f32) * t).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
 }
 
-pub fn part2(input: &Input) -> u32 {
-    let map = Map::new(input);
+/// heatmaps of the part 1 and part 2 routes side by side, each route drawn as arrows
+/// with the heat lost so far at every turn
+pub fn render_svg(input: &Input) -> String {
+    const CELL: usize = 16;
+    let routes =
+        [("part 1", Crucible::REGULAR), ("part 2", Crucible::ULTRA)].map(|(name, crucible)| {
+            let route = crucible
+                .solve(input)
+                .unwrap_or_else(|| panic!("no path found"));
+            (name, route)
+        });
+    let (coolest, hottest) = input
+        .iter()
+        .flatten()
+        .minmax()
+        .into_option()
+        .map_or((0, 0), |(low, high)| (*low, *high));
+    let (panel, top) = (input.columns * CELL, 2 * CELL);
+    let (width, height) = (2 * panel + CELL, input.rows * CELL + top);
 
-    let filter_by = |neighbor: &(Block, u32), block: &Block| -> bool {
-        if block.direction == Direction::Start {
-            return true;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg += "<style>line { stroke: #1040c0; stroke-width: 3; stroke-linecap: round; } \
+        text { font: 10px sans-serif; text-anchor: middle; dominant-baseline: middle; } \
+        .title { font-size: 14px; } \
+        .cost { fill: #1040c0; stroke: white; stroke-width: 3; paint-order: stroke; }</style>\n";
+    svg += "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"8\" refY=\"5\" \
+        markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\">\
+        <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#1040c0\"/></marker></defs>\n";
+    svg += &format!("<rect width=\"{width}\" height=\"{height}\" fill=\"white\"/>\n");
+
+    for (i, (name, route)) in routes.iter().enumerate() {
+        let left = i * (panel + CELL);
+        let centre = |(row, col): (usize, usize)| {
+            (left + col * CELL + CELL / 2, top + row * CELL + CELL / 2)
+        };
+
+        svg += &format!(
+            "<text class=\"title\" x=\"{}\" y=\"{CELL}\">{name}: {}</text>\n",
+            left + panel / 2,
+            route.heat_loss
+        );
+        for (row, blocks) in input.iter().enumerate() {
+            for (col, heat) in blocks.iter().enumerate() {
+                let (x, y) = (left + col * CELL, top + row * CELL);
+                svg += &format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"{}\"/>\n",
+                    heat_colour(*heat, coolest, hottest)
+                );
+            }
         }
 
-        if neighbor.0.blocks > 10 {
-            return false;
+        let corners = route.corners(input);
+        for ((from, _), (to, _)) in corners.iter().tuple_windows() {
+            let ((x1, y1), (x2, y2)) = (centre(*from), centre(*to));
+            svg += &format!(
+                "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" marker-end=\"url(#arrow)\"/>\n"
+            );
         }
-        if block.blocks < 4 {
-            return block.direction == neighbor.0.direction;
+        for (block, heat_loss) in corners.iter().skip(1) {
+            let (x, y) = centre(*block);
+            svg += &format!("<text class=\"cost\" x=\"{x}\" y=\"{y}\">{heat_loss}</text>\n");
         }
-        true
-    };
-
-    let path = astar(
-        &map.start,
-        |block| map.eligible_neighbors(block, filter_by),
-        |block| map.heuristic(block),
-        |block| map.end.eq(&block.coord) && block.blocks >= 4,
-    )
-    .unwrap_or_else(|| panic!("no path found"));
+    }
 
-    // print_grid(&map.grid, &path.0);
-    path.1
+    svg + "</svg>\n"
 }
 
 #[allow(dead_code)]
-fn print_grid(matrix: &Input, path: &[Block]) {
-    let mut grid = matrix
-        .iter()
-        .map(|row: &[u32]| row.iter().map(|c| c.to_string()).collect_vec())
-        .collect_vec();
-
-    path.iter().for_each(|block| {
-        grid[block.coord.row][block.coord.col] = match block.direction {
-            Direction::Up => String::from("^"),
-            Direction::Down => String::from("v"),
-            Direction::Left => String::from("<"),
-            Direction::Right => String::from(">"),
-            _ => grid[block.coord.row][block.coord.col].clone(),
-        }
-    });
-
-    for row in grid.iter() {
-        println!("{:?}", row.iter().join(""));
-    }
+pub fn save_svg(input: &Input, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, render_svg(input))
 }
 
 #[cfg(test)]
@@ -248,4 +302,68 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 94);
         assert_eq!(part2(&generator(SAMPLE2)), 71);
     }
+
+    /// checks the path is connected and every straight run is within the crucible's limits
+    fn check_route(crucible: &Crucible, input: &Input, route: &Route) {
+        assert_eq!(route.path.first(), Some(&(0, 0)));
+        assert_eq!(
+            route.path.last(),
+            Some(&(input.rows - 1, input.columns - 1))
+        );
+        let steps = route
+            .path
+            .iter()
+            .tuple_windows()
+            .map(|(a, b)| (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize))
+            .collect_vec();
+        assert!(steps.iter().all(|(r, c)| r.abs() + c.abs() == 1));
+        for run in steps.chunk_by(|a, b| a == b) {
+            assert!((crucible.min_run..=crucible.max_run).contains(&run.len()));
+        }
+        assert_eq!(
+            route.cost,
+            route.heat_loss + route.turns() as u32 * crucible.turn_cost
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        for input in [generator(SAMPLE), generator(SAMPLE2)] {
+            for crucible in [Crucible::REGULAR, Crucible::ULTRA] {
+                check_route(&crucible, &input, &crucible.solve(&input).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_turn_cost() {
+        let input = generator(SAMPLE);
+        let crucible = Crucible {
+            turn_cost: 1000,
+            ..Crucible::REGULAR
+        };
+        let route = crucible.solve(&input).unwrap();
+        check_route(&crucible, &input, &route);
+        // 12 down and 12 right in runs of at most 3 takes at least 8 runs
+        assert_eq!(route.turns(), 7);
+        assert!(route.heat_loss >= 102);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = generator(SAMPLE);
+        let corners = Crucible::REGULAR.solve(&input).unwrap().corners(&input);
+        assert_eq!(corners.first(), Some(&((0, 0), 0)));
+        assert_eq!(corners.last(), Some(&((12, 12), 102)));
+        assert!(corners.iter().tuple_windows().all(|(a, b)| a.1 < b.1));
+
+        let svg = render_svg(&input);
+        assert!(svg.contains(">part 1: 102<") && svg.contains(">part 2: 94<"));
+        assert_eq!(svg.matches("<rect x=").count(), 2 * 13 * 13);
+        assert_eq!(
+            svg.matches("<line ").count(),
+            svg.matches("class=\"cost\" x=").count()
+        );
+        assert_eq!(svg.matches(&heat_colour(9, 1, 9)).count(), 2 * 9);
+    }
 }
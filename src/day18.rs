@@ -26,7 +26,7 @@ pub struct Trench<'a> {
     end: Coord,
 }
 
-pub fn generator(input: &str) -> Input {
+pub fn generator(input: &str) -> Input<'_> {
     let re = Regex::new(r"\w{6}").unwrap();
 
     input
@@ -1,3 +1,6 @@
+#[cfg(test)]
+mod bench;
+mod cycle;
 mod day01;
 mod day02;
 mod day03;
@@ -16,6 +19,9 @@ mod day15;
 mod day16;
 mod day17;
 mod day18;
+mod nonogram;
+mod sequence;
+mod symmetry;
 
 aoc_main::main! {
   year 2023;
@@ -0,0 +1,168 @@
+use crate::day12::chomp;
+use itertools::Itertools;
+
+/// A nonogram puzzle as row and column clues, each line solved like a day12 record
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, PartialEq)]
+pub struct Solved {
+    /// rows of '#' and '.'
+    pub grid: Vec<String>,
+    pub unique: bool,
+}
+
+type Cells = Vec<Vec<u8>>;
+
+impl Nonogram {
+    #[allow(dead_code)]
+    pub fn new(rows: Vec<Vec<usize>>, cols: Vec<Vec<usize>>) -> Self {
+        // a lone 0 clue is an empty line
+        let clean = |clues: Vec<Vec<usize>>| {
+            clues
+                .into_iter()
+                .map(|line| line.into_iter().filter(|clue| *clue > 0).collect_vec())
+                .collect_vec()
+        };
+        Nonogram {
+            rows: clean(rows),
+            cols: clean(cols),
+        }
+    }
+
+    /// the first solution found, and whether it is the only one
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Option<Solved> {
+        let mut solutions = Vec::new();
+        self.search(
+            vec![vec![b'?'; self.cols.len()]; self.rows.len()],
+            &mut solutions,
+        );
+
+        let unique = solutions.len() == 1;
+        solutions.into_iter().next().map(|cells| Solved {
+            grid: cells
+                .into_iter()
+                .map(|row| String::from_utf8(row).unwrap())
+                .collect(),
+            unique,
+        })
+    }
+
+    /// propagate, then guess the first unknown cell, stopping at a second solution
+    fn search(&self, cells: Cells, solutions: &mut Vec<Cells>) {
+        let Some(cells) = self.propagate(cells) else {
+            return;
+        };
+
+        let unknown = cells.iter().enumerate().find_map(|(row, line)| {
+            line.iter()
+                .position(|cell| *cell == b'?')
+                .map(|col| (row, col))
+        });
+        match unknown {
+            None => solutions.push(cells),
+            Some((row, col)) => {
+                for guess in [b'#', b'.'] {
+                    if solutions.len() > 1 {
+                        return;
+                    }
+                    let mut next = cells.clone();
+                    next[row][col] = guess;
+                    self.search(next, solutions);
+                }
+            }
+        }
+    }
+
+    /// settle every cell the line clues force, None on a contradiction
+    fn propagate(&self, mut cells: Cells) -> Option<Cells> {
+        loop {
+            let mut changed = false;
+
+            for (line, clues) in cells.iter_mut().zip(&self.rows) {
+                changed |= deduce(line, clues)?;
+            }
+            for (col, clues) in self.cols.iter().enumerate() {
+                let mut line = cells.iter().map(|row| row[col]).collect_vec();
+                changed |= deduce(&mut line, clues)?;
+                for (row, cell) in cells.iter_mut().zip(line) {
+                    row[col] = cell;
+                }
+            }
+
+            if !changed {
+                return Some(cells);
+            }
+        }
+    }
+}
+
+/// fix each unknown cell that only one value can take, None if the line is impossible
+fn deduce(line: &mut [u8], clues: &[usize]) -> Option<bool> {
+    if chomp(line, clues) == 0 {
+        return None;
+    }
+
+    let mut changed = false;
+    for i in 0..line.len() {
+        if line[i] != b'?' {
+            continue;
+        }
+        line[i] = b'#';
+        let filled = chomp(line, clues);
+        line[i] = b'.';
+        let empty = chomp(line, clues);
+
+        line[i] = match (filled, empty) {
+            (0, _) => b'.',
+            (_, 0) => b'#',
+            _ => b'?',
+        };
+        changed |= line[i] != b'?';
+    }
+
+    Some(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique() {
+        let puzzle = Nonogram::new(
+            vec![vec![1], vec![3], vec![1, 1], vec![0]],
+            vec![vec![2], vec![2], vec![2]],
+        );
+        assert_eq!(
+            puzzle.solve(),
+            Some(Solved {
+                grid: vec![
+                    String::from(".#."),
+                    String::from("###"),
+                    String::from("#.#"),
+                    String::from("..."),
+                ],
+                unique: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        // the two diagonals need a guess to tell apart
+        let puzzle = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let solved = puzzle.solve().unwrap();
+        assert_eq!(solved.grid, vec![String::from("#."), String::from(".#")]);
+        assert!(!solved.unique);
+
+        let impossible = Nonogram::new(vec![vec![2], vec![0]], vec![vec![0], vec![1]]);
+        assert_eq!(impossible.solve(), None);
+    }
+}
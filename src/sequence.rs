@@ -0,0 +1,109 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Zero};
+
+/// Minimal-degree polynomial through a sequence sampled at 0, 1, 2...,
+/// kept in Newton form as the leading forward differences
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    differences: Vec<BigInt>,
+}
+
+impl Polynomial {
+    pub fn fit(values: &[isize]) -> Self {
+        let mut current = values
+            .iter()
+            .map(|value| BigInt::from(*value))
+            .collect::<Vec<_>>();
+        let mut differences = Vec::new();
+
+        // stop once a whole row is zero, not when it sums to zero
+        while current.iter().any(|value| !value.is_zero()) {
+            differences.push(current[0].clone());
+            current = current.windows(2).map(|pair| &pair[1] - &pair[0]).collect();
+        }
+
+        Polynomial { differences }
+    }
+
+    /// None for the zero polynomial
+    #[allow(dead_code)]
+    pub fn degree(&self) -> Option<usize> {
+        self.differences
+            .iter()
+            .rposition(|difference| !difference.is_zero())
+    }
+
+    /// value at any offset from the first sample, before or after the sequence
+    pub fn at(&self, offset: isize) -> BigInt {
+        let offset = BigInt::from(offset);
+        let mut binomial = BigInt::one();
+        let mut value = BigInt::zero();
+
+        for (j, difference) in self.differences.iter().enumerate() {
+            value += &binomial * difference;
+            // offset choose j + 1, exact for negative offsets too
+            binomial = binomial * (&offset - j) / (j + 1);
+        }
+
+        value
+    }
+
+    /// coefficients in the monomial basis, constant term first
+    #[allow(dead_code)]
+    pub fn coefficients(&self) -> Vec<BigRational> {
+        let mut coefficients = vec![BigRational::zero(); self.degree().map_or(0, |d| d + 1)];
+        // falling factorial x(x - 1)...(x - j + 1) and j!
+        let mut falling = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+
+        for (j, difference) in self.differences.iter().enumerate() {
+            for (coefficient, term) in coefficients.iter_mut().zip(&falling) {
+                *coefficient += BigRational::new(term * difference, factorial.clone());
+            }
+
+            let mut next = vec![BigInt::zero(); falling.len() + 1];
+            for (i, term) in falling.iter().enumerate() {
+                next[i + 1] += term;
+                next[i] -= term * j;
+            }
+            falling = next;
+            factorial *= j + 1;
+        }
+
+        coefficients
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(numer: isize, denom: isize) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    #[test]
+    fn test_fit() {
+        let triangular = Polynomial::fit(&[1, 3, 6, 10, 15, 21]);
+        assert_eq!(triangular.degree(), Some(2));
+        assert_eq!(triangular.at(6), 28.into());
+        assert_eq!(triangular.at(-1), 0.into());
+        assert_eq!(triangular.at(-3), 1.into());
+        assert_eq!(
+            triangular.coefficients(),
+            vec![ratio(1, 1), ratio(3, 2), ratio(1, 2)]
+        );
+
+        // sums to zero without being zero
+        let alternating = Polynomial::fit(&[1, -1]);
+        assert_eq!(alternating.degree(), Some(1));
+        assert_eq!(alternating.at(2), (-3).into());
+
+        assert_eq!(Polynomial::fit(&[0, 0, 0]).degree(), None);
+        assert_eq!(
+            Polynomial::fit(&[isize::MAX, 0, isize::MIN]).at(3),
+            BigInt::from(isize::MIN) * 2 - 1
+        );
+    }
+}
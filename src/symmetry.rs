@@ -0,0 +1,245 @@
+use itertools::Itertools;
+
+/// A grid pattern of '#' and '.', with its columns kept alongside the rows
+#[derive(Debug)]
+pub struct Pattern {
+    rows: Vec<Line>,
+    cols: Vec<Line>,
+}
+
+/// One row or column with a bit set for each '#', 64 cells to a word
+#[derive(Debug, Clone, PartialEq)]
+struct Line(Vec<u64>);
+
+impl Line {
+    fn new(cells: impl ExactSizeIterator<Item = bool>) -> Self {
+        let mut words = vec![0; cells.len().div_ceil(64)];
+        for (i, cell) in cells.enumerate() {
+            words[i / 64] |= (cell as u64) << (i % 64);
+        }
+        Line(words)
+    }
+
+    #[allow(dead_code)]
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn mismatches(&self, other: &Line) -> usize {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Symmetry {
+    /// mirror line between two columns, with this many columns to its left
+    Vertical(usize),
+    /// mirror line between two rows, with this many rows above it
+    Horizontal(usize),
+    /// mirrored across the top left to bottom right diagonal
+    MainDiagonal,
+    /// mirrored across the top right to bottom left diagonal
+    AntiDiagonal,
+    /// unchanged by a half turn
+    Rotational,
+}
+
+impl Symmetry {
+    /// columns left of a vertical line, or 100 times the rows above a horizontal one
+    pub fn summary(&self) -> Option<usize> {
+        match self {
+            Symmetry::Vertical(cols) => Some(*cols),
+            Symmetry::Horizontal(rows) => Some(rows * 100),
+            _ => None,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new<'a>(lines: impl Iterator<Item = &'a str>) -> Self {
+        let lines = lines.map(str::as_bytes).collect_vec();
+        let width = lines.first().map_or(0, |line| line.len());
+
+        let rows = lines
+            .iter()
+            .map(|line| Line::new(line.iter().map(|cell| *cell == b'#')))
+            .collect();
+        let cols = (0..width)
+            .map(|col| Line::new(lines.iter().map(|line| line[col] == b'#')))
+            .collect();
+        Pattern { rows, cols }
+    }
+
+    #[allow(dead_code)]
+    fn cell(&self, row: usize, col: usize) -> bool {
+        self.rows[row].get(col)
+    }
+
+    /// mirror lines where exactly `smudges` cell pairs disagree, columns first
+    pub fn reflections(&self, smudges: usize) -> Vec<Symmetry> {
+        let vertical = mirror_lines(&self.cols, smudges).map(Symmetry::Vertical);
+        let horizontal = mirror_lines(&self.rows, smudges).map(Symmetry::Horizontal);
+        vertical.chain(horizontal).collect()
+    }
+
+    /// every reflection plus diagonal and half turn symmetry with exactly `smudges` mismatches
+    #[allow(dead_code)]
+    pub fn symmetries(&self, smudges: usize) -> Vec<Symmetry> {
+        let (height, width) = (self.rows.len(), self.cols.len());
+        let mut symmetries = self.reflections(smudges);
+
+        if height == width {
+            let main = (0..height)
+                .tuple_combinations()
+                .filter(|(a, b)| self.cell(*a, *b) != self.cell(*b, *a))
+                .count();
+            let anti = (0..height)
+                .cartesian_product(0..width)
+                .filter(|(row, col)| row + col < width - 1)
+                .filter(|(row, col)| {
+                    self.cell(*row, *col) != self.cell(width - 1 - col, height - 1 - row)
+                })
+                .count();
+            if main == smudges {
+                symmetries.push(Symmetry::MainDiagonal);
+            }
+            if anti == smudges {
+                symmetries.push(Symmetry::AntiDiagonal);
+            }
+        }
+
+        // each cell in the first half against its partner, the centre pairs with itself
+        let rotational = (0..height * width / 2)
+            .filter(|i| {
+                let (row, col) = (i / width, i % width);
+                self.cell(row, col) != self.cell(height - 1 - row, width - 1 - col)
+            })
+            .count();
+        if rotational == smudges {
+            symmetries.push(Symmetry::Rotational);
+        }
+
+        symmetries
+    }
+}
+
+/// positions between lines where folding leaves exactly `smudges` mismatched cells
+fn mirror_lines(lines: &[Line], smudges: usize) -> impl Iterator<Item = usize> + '_ {
+    (1..lines.len())
+        .filter(move |split| fold_mismatches(lines, *split, Line::mismatches) == smudges)
+}
+
+/// mismatched cells when folding `lines` between `split - 1` and `split`
+fn fold_mismatches<L>(lines: &[L], split: usize, mismatches: impl Fn(&L, &L) -> usize) -> usize {
+    let (before, after) = lines.split_at(split);
+    before
+        .iter()
+        .rev()
+        .zip(after)
+        .map(|(a, b)| mismatches(a, b))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_symmetries() {
+        let pattern = Pattern::new(["#..", ".#.", "..#"].into_iter());
+        assert_eq!(
+            pattern.symmetries(0),
+            vec![
+                Symmetry::MainDiagonal,
+                Symmetry::AntiDiagonal,
+                Symmetry::Rotational
+            ]
+        );
+
+        let pattern = Pattern::new(["##.#", "#..#", "##.#"].into_iter());
+        assert_eq!(pattern.reflections(0), vec![]);
+        assert_eq!(
+            pattern.reflections(1),
+            vec![
+                Symmetry::Vertical(1),
+                Symmetry::Horizontal(1),
+                Symmetry::Horizontal(2)
+            ]
+        );
+        assert_eq!(pattern.reflections(2), vec![Symmetry::Vertical(2)]);
+        assert_eq!(pattern.reflections(3), vec![Symmetry::Vertical(3)]);
+    }
+
+    #[test]
+    fn test_wide() {
+        let half = "#.".repeat(50) + "##.";
+        let row = format!("{half}{}", half.chars().rev().collect::<String>());
+        let pattern = Pattern::new([row.as_str(), row.as_str()].into_iter());
+        assert_eq!(
+            pattern.reflections(0),
+            vec![Symmetry::Vertical(103), Symmetry::Horizontal(1)]
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_bitmasks() {
+        let mut rng = bench::rng(13);
+        let patterns = (0..100)
+            .map(|_| {
+                (0..300)
+                    .map(|_| {
+                        (0..300)
+                            .map(|_| *[b'#', b'.'].choose(&mut rng).unwrap() as char)
+                            .collect::<String>()
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // every fold of the columns then rows, only the line comparison differs
+        fn folds<L>(lines: [&[L]; 2], mismatches: impl Fn(&L, &L) -> usize + Copy) -> Vec<usize> {
+            lines
+                .iter()
+                .flat_map(|lines| {
+                    (1..lines.len()).map(move |split| fold_mismatches(lines, split, mismatches))
+                })
+                .collect()
+        }
+
+        let (strings, bitmasks) = bench::compare(
+            "string lines to bitmasks",
+            || {
+                patterns
+                    .iter()
+                    .map(|rows| {
+                        let cols = (0..rows[0].len())
+                            .map(|col| rows.iter().map(|row| &row[col..=col]).collect::<String>())
+                            .collect_vec();
+                        folds([&cols, rows], |a: &String, b| {
+                            a.chars().zip(b.chars()).filter(|(a, b)| a != b).count()
+                        })
+                    })
+                    .collect_vec()
+            },
+            || {
+                patterns
+                    .iter()
+                    .map(|rows| {
+                        let pattern = Pattern::new(rows.iter().map(String::as_str));
+                        folds([&pattern.cols, &pattern.rows], Line::mismatches)
+                    })
+                    .collect_vec()
+            },
+        );
+
+        assert_eq!(strings, bitmasks);
+    }
+}